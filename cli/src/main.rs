@@ -1,19 +1,36 @@
 use std::collections::HashMap;
 use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::vec;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, Result};
+use clap::{Parser, ValueEnum};
 use comfy_table::Table;
-use crucible_core::combat::build_level_one_combat;
+use crucible_core::battlefield::Cover;
+use crucible_core::combat::{level_one_encounter, Cause, Roll};
+use crucible_core::day::AdventuringDay;
+use crucible_core::dnd::builder::EncounterBuilder;
 use crucible_core::dnd::mcdm::MCDMDifficultyCalculator;
+use crucible_core::dnd::simulated::{SimulatedDifficulty, SimulatedOutcome};
 use crucible_core::dnd::wizards::{WizardDifficultyCalculator2014, WizardDifficultyCalculator2024};
-use crucible_core::dnd::DifficultyCalculator;
-use crucible_core::monte_carlo::combat::combat_monte_carlo_iterator;
+use crucible_core::dnd::{DifficultyCalculator, TableCalculator};
+use crucible_core::encounter::Encounter;
+use crucible_core::monte_carlo::calibration::{calibration_iterator, cross_tab};
+use crucible_core::monte_carlo::combat::{
+    combat_monte_carlo_iterator, combat_monte_carlo_until_converged, CombatStats,
+};
+use crucible_core::monte_carlo::compare::compare_iterator;
+use crucible_core::monte_carlo::day::day_monte_carlo_iterator;
 use crucible_core::monte_carlo::dice::dice_monte_carlo_iterator;
-use crucible_core::{Action, ActionResult, Character, HitResult};
+use crucible_core::monte_carlo::stats::{ConfidenceInterval, Distribution, PairedDifference};
+use crucible_core::monte_carlo::sweep::{sweep_iterator, SweepParameter, SweepPoint};
+use crucible_core::recording::Recording;
+use crucible_core::{Action, Combat, Event};
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 
+const HISTOGRAM_BUCKETS: usize = 12;
+
 #[derive(Parser)]
 enum SubCommand {
     #[command(name = "dice-monte-carlo")]
@@ -24,6 +41,24 @@ enum SubCommand {
 
     #[command(name = "level-1-monte-carlo")]
     LevelOneMonteCarlo(LevelOneMonteCarloArgs),
+
+    #[command(name = "sweep")]
+    Sweep(SweepArgs),
+
+    #[command(name = "compare")]
+    Compare(CompareArgs),
+
+    #[command(name = "calibrate")]
+    Calibrate(CalibrateArgs),
+
+    #[command(name = "build-encounter")]
+    BuildEncounter(BuildEncounterArgs),
+
+    #[command(name = "day")]
+    Day(DayArgs),
+
+    #[command(name = "replay")]
+    Replay(ReplayArgs),
 }
 
 #[derive(Parser)]
@@ -40,6 +75,14 @@ pub struct LevelOneKoboldsArgs {
     /// Number of iterations for each die type
     #[arg(short, long, default_value = "6")]
     num_kobolds: usize,
+
+    /// Seed for the dice. Random when omitted
+    #[arg(short, long)]
+    seed: Option<u64>,
+
+    /// Save the fight to this file to replay later
+    #[arg(short, long)]
+    record: Option<PathBuf>,
 }
 
 #[derive(Parser)]
@@ -56,6 +99,190 @@ pub struct LevelOneMonteCarloArgs {
     /// Number of iterations for each die type
     #[arg(short, long, default_value = "6")]
     num_kobolds: usize,
+
+    /// Keep simulating until every 95% confidence interval is narrower than
+    /// this (e.g. 0.02 for +/- 1%) instead of running a fixed number of iterations
+    #[arg(short, long)]
+    target_width: Option<f64>,
+
+    /// Upper bound on iterations when simulating to a target width
+    #[arg(long, default_value = "1000000")]
+    max_iterations: usize,
+
+    /// Base seed; combat N is rolled from seed + N. Random when omitted
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Save recordings of this many of the quickest party wipes and of the
+    /// longest fights
+    #[arg(long, default_value = "0")]
+    save_extremes: usize,
+
+    /// Directory the recordings are saved to
+    #[arg(long, default_value = ".")]
+    save_dir: PathBuf,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SweepParam {
+    MonsterCount,
+    PartyLevel,
+    MonsterAc,
+    MonsterHp,
+    MonsterHitBonus,
+}
+
+#[derive(Parser)]
+#[command(name = "sweep")]
+pub struct SweepArgs {
+    /// Encounter file (TOML); defaults to the level 1 kobold encounter
+    #[arg(short, long)]
+    encounter: Option<PathBuf>,
+
+    /// Parameter to vary
+    #[arg(short, long, value_enum)]
+    param: SweepParam,
+
+    /// Monster the parameter applies to; may be omitted when the encounter
+    /// only has one kind of monster
+    #[arg(short, long)]
+    monster: Option<String>,
+
+    /// First value of the parameter
+    #[arg(long, allow_negative_numbers = true)]
+    from: isize,
+
+    /// Last value of the parameter (inclusive)
+    #[arg(long, allow_negative_numbers = true)]
+    to: isize,
+
+    /// Step between values
+    #[arg(long, default_value = "1")]
+    step: usize,
+
+    /// Number of combats simulated for each value
+    #[arg(short, long, default_value = "2000")]
+    iterations: usize,
+
+    /// Print CSV instead of a table
+    #[arg(long, default_value = "false")]
+    csv: bool,
+}
+
+#[derive(Parser)]
+#[command(name = "compare")]
+pub struct CompareArgs {
+    /// Encounter file (TOML) for variant A
+    a: PathBuf,
+
+    /// Encounter file (TOML) for variant B
+    b: PathBuf,
+
+    /// Number of combats simulated for each variant
+    #[arg(short, long, default_value = "5000")]
+    iterations: usize,
+
+    /// Base seed; combat N of each variant is rolled from seed + N.
+    /// Random when omitted
+    #[arg(short, long)]
+    seed: Option<u64>,
+}
+
+#[derive(Parser)]
+#[command(name = "calibrate")]
+pub struct CalibrateArgs {
+    /// Number of random encounters to generate
+    #[arg(short, long, default_value = "200")]
+    encounters: usize,
+
+    /// Number of combats simulated for each encounter
+    #[arg(short, long, default_value = "500")]
+    iterations: usize,
+
+    /// Highest party level to generate
+    #[arg(short, long, default_value = "10")]
+    max_level: usize,
+
+    /// Seed for generating and simulating the encounters. Random when omitted
+    #[arg(short, long)]
+    seed: Option<u64>,
+
+    /// Print one CSV row per encounter instead of the summary tables
+    #[arg(long, default_value = "false")]
+    csv: bool,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Rules {
+    Mcdm,
+    Wizards2014,
+    Wizards2024,
+}
+
+impl From<Rules> for TableCalculator {
+    fn from(value: Rules) -> Self {
+        match value {
+            Rules::Mcdm => TableCalculator::Mcdm,
+            Rules::Wizards2014 => TableCalculator::Wizards2014,
+            Rules::Wizards2024 => TableCalculator::Wizards2024,
+        }
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "build-encounter")]
+pub struct BuildEncounterArgs {
+    /// Party member levels, e.g. 3,3,4,4
+    #[arg(short, long, value_delimiter = ',', required = true)]
+    party: Vec<usize>,
+
+    /// Target difficulty, named as the chosen rules name it (e.g. hard for
+    /// wizards2014, moderate for wizards2024)
+    #[arg(short, long)]
+    difficulty: String,
+
+    /// Which budget table to build against
+    #[arg(short, long, value_enum, default_value = "wizards2014")]
+    rules: Rules,
+
+    /// Encounter file (TOML) whose monsters make up the pool to pick from
+    #[arg(short, long)]
+    monsters: PathBuf,
+
+    /// Number of encounters to suggest
+    #[arg(short, long, default_value = "5")]
+    count: usize,
+
+    /// Most monsters in one encounter
+    #[arg(long, default_value = "8")]
+    max_monsters: usize,
+
+    /// Most different kinds of monster in one encounter
+    #[arg(long, default_value = "2")]
+    max_kinds: usize,
+
+    /// Check each suggestion with this many simulated combats against
+    /// generic heroes of the party's levels
+    #[arg(short, long)]
+    verify: Option<usize>,
+}
+
+#[derive(Parser)]
+#[command(name = "day")]
+pub struct DayArgs {
+    /// Adventuring day file (TOML): a party and the encounters they face in order
+    day: PathBuf,
+
+    /// Number of days to simulate
+    #[arg(short, long, default_value = "2000")]
+    iterations: usize,
+}
+
+#[derive(Parser)]
+#[command(name = "replay")]
+pub struct ReplayArgs {
+    /// Recording file (TOML) saved by level-1-kobolds or level-1-monte-carlo
+    recording: PathBuf,
 }
 
 #[derive(Parser)]
@@ -94,39 +321,56 @@ fn main() -> Result<()> {
         SubCommand::DiceMonteCarlo(args) => dice_monte_carlo(args),
         SubCommand::LevelOneKobolds(args) => level_one_kobolds(args),
         SubCommand::LevelOneMonteCarlo(args) => level_one_monte_carlo(args),
+        SubCommand::Sweep(args) => sweep(args),
+        SubCommand::Compare(args) => compare(args),
+        SubCommand::Calibrate(args) => calibrate(args),
+        SubCommand::BuildEncounter(args) => build_encounter(args),
+        SubCommand::Day(args) => day(args),
+        SubCommand::Replay(args) => replay(args),
     }
 }
 
 fn level_one_kobolds(args: LevelOneKoboldsArgs) -> Result<()> {
-    let mut combat = build_level_one_combat(args.num_kobolds);
-    combat.debug(true);
-    let mut table = Table::new();
-    table.set_header(vec![
-        "Round", "Actor", "Action", "Target", "Result", "All Hps", "All ACs",
-    ]);
+    let encounter = level_one_encounter(args.num_kobolds);
+    let seed = args.seed.unwrap_or_else(random_seed);
+    let (recording, combat) = Recording::record(&encounter, seed);
 
-    combat.roll_initiative();
-
-    while combat.is_ongoing() {
-        combat.execute_round();
+    print_fight(&encounter, &combat, &recording.events);
+    println!("Seed: {}", seed);
+    if let Some(path) = args.record {
+        recording.save(&path)?;
+        println!("Saved to {}", path.display());
     }
+    Ok(())
+}
 
-    for log in combat.debug_log {
-        table.add_row(vec![
-            format!("{}", log.round),
-            actor_name(log.actor),
-            action_name(log.action),
-            actor_name(log.target),
-            result_debug(log.result),
-            all_hps(&log.snapshot_heroes, &log.snapshot_monsters),
-            all_acs(&log.snapshot_heroes, &log.snapshot_monsters),
-        ]);
-    }
+fn replay(args: ReplayArgs) -> Result<()> {
+    let recording = Recording::load(&args.recording)?;
+    let replay = recording.replay();
 
-    println!("{table}");
+    print_fight(&recording.encounter, &replay.combat, &replay.events);
+    println!("Seed: {}", recording.seed);
+    if let Some(index) = replay.diverged_at {
+        println!(
+            "The replay differs from the recording from event {} on; \
+             the simulator may have changed since it was recorded",
+            index + 1
+        );
+    }
     Ok(())
 }
 
+/// The turn-by-turn table of a finished fight
+fn print_fight(encounter: &Encounter, combat: &Combat, events: &[Event]) {
+    println!(
+        "{}",
+        event_table(events, starting_hps(&encounter.build_combat()))
+    );
+    if combat.is_draw() {
+        println!("Draw after {} rounds", combat.rounds_fought());
+    }
+}
+
 fn dice_monte_carlo(args: DiceMonteCarloArgs) -> Result<()> {
     let mut table = Table::new();
     table.set_header(vec![
@@ -140,7 +384,7 @@ fn dice_monte_carlo(args: DiceMonteCarloArgs) -> Result<()> {
     let mut iterator = dice_monte_carlo_iterator(args.iterations);
     let bar = ProgressBar::new(iterator.clone().count() as u64);
 
-    while let Some(_) = iterator.next() {
+    for _ in iterator.by_ref() {
         bar.inc(1);
     }
     bar.finish_and_clear();
@@ -163,21 +407,46 @@ fn dice_monte_carlo(args: DiceMonteCarloArgs) -> Result<()> {
 }
 
 fn level_one_monte_carlo(args: LevelOneMonteCarloArgs) -> Result<()> {
+    if args.save_extremes > 0 {
+        // Before the batch, so a bad directory doesn't throw away the run
+        std::fs::create_dir_all(&args.save_dir)?;
+    }
     let mut table = Table::new();
     table.set_header(vec![
+        "Iterations",
         "Player Victories",
         "Monster Victories",
+        "Draws",
         "Average Rounds",
         "Hero K/O Counts",
         "Monster K/O Counts",
         "Decisive Victories",
-        //"Pyrrhic Victories",
+        "Pyrrhic Victories",
+        "Routs",
+        "Wipes",
         "MCDM Difficulty",
         "Wizards Difficulty",
+        "Simulated Difficulty",
     ]);
 
-    let mut iterator = combat_monte_carlo_iterator(args.iterations, args.verbose, args.num_kobolds);
-    let bar = ProgressBar::new(args.iterations as u64);
+    let (mut iterator, max_iterations) = match args.target_width {
+        Some(target_width) => (
+            combat_monte_carlo_until_converged(
+                target_width,
+                args.max_iterations,
+                args.verbose,
+                args.num_kobolds,
+            ),
+            args.max_iterations,
+        ),
+        None => (
+            combat_monte_carlo_iterator(args.iterations, args.verbose, args.num_kobolds),
+            args.iterations,
+        ),
+    };
+    let seed = args.seed.unwrap_or_else(random_seed);
+    iterator = iterator.with_seed(seed).with_extremes(args.save_extremes);
+    let bar = ProgressBar::new(max_iterations as u64);
     bar.set_style(
         ProgressStyle::with_template(
             "{spinner:.green} [{elapsed_precise}] [{wide_bar:.cyan/blue}] ({eta})",
@@ -189,52 +458,625 @@ fn level_one_monte_carlo(args: LevelOneMonteCarloArgs) -> Result<()> {
         .progress_chars("#>-"),
     );
 
-    while let Some(_) = iterator.next() {
+    for _ in iterator.by_ref() {
         bar.inc(1);
     }
     bar.finish_and_clear();
 
-    let combat = build_level_one_combat(args.num_kobolds);
-    let mcdm: DifficultyCalculator<MCDMDifficultyCalculator> =
-        DifficultyCalculator::new(combat.hero_levels(), combat.monster_crs());
-    let wizards2014: DifficultyCalculator<WizardDifficultyCalculator2014> =
-        DifficultyCalculator::new(combat.hero_levels(), combat.monster_crs());
-    let wizards2024: DifficultyCalculator<WizardDifficultyCalculator2024> =
-        DifficultyCalculator::new(combat.hero_levels(), combat.monster_crs());
+    let encounter = level_one_encounter(args.num_kobolds);
+    let (mcdm, wizards2014, wizards2024) =
+        difficulties(encounter.hero_levels(), encounter.monster_crs())?;
 
     let stats = iterator.stats;
+    let simulated = SimulatedOutcome::from_stats(&encounter, &stats);
     table.add_row(vec![
-        format!(
-            "{} ({:.3}%)",
+        format!("{}", stats.iterations),
+        victories_formatted(
             stats.hero_victories,
-            stats.hero_victories_perc * 100.0
+            stats.hero_victories_perc,
+            &stats.hero_victories_ci,
         ),
-        format!(
-            "{} ({:.3}%)",
+        victories_formatted(
             stats.monster_victories,
-            stats.monster_victories_perc * 100.0
+            stats.monster_victories_perc,
+            &stats.monster_victories_ci,
+        ),
+        victories_formatted(stats.draws, stats.draws_perc, &stats.draws_ci),
+        format!(
+            "{:.3} ± {:.3}",
+            stats.average_rounds, stats.average_rounds_std_err
         ),
-        format!("{:.3}", stats.average_rounds),
         actor_ko_counts_formatted(&stats.hero_ko_counts),
         actor_ko_counts_formatted(&stats.monster_ko_counts),
-        format!(
-            "{} ({:.3}%)",
+        victories_formatted(
             stats.decisive_victories,
-            stats.decisive_victories_perc * 100.0
+            stats.decisive_victories_perc,
+            &stats.decisive_victories_ci,
+        ),
+        victories_formatted(
+            stats.pyrrhic_victories,
+            stats.pyrrhic_victories_perc,
+            &stats.pyrrhic_victories_ci,
+        ),
+        format!("{} ({:.3}%)", stats.routs, stats.routs_perc * 100.0),
+        format!("{} ({:.3}%)", stats.wipes, stats.wipes_perc * 100.0),
+        mcdm,
+        format!("2014: {}\n2024: {}", wizards2014, wizards2024),
+        simulated_formatted(&simulated),
+    ]);
+
+    println!("{table}");
+    println!("{}", distributions_table(&stats));
+    println!("{}", contributions_table(&stats));
+
+    println!("Seed: {}", seed);
+    let extremes = &iterator.extremes;
+    for (kind, runs) in [
+        ("fastest-wipe", &extremes.fastest_wipes),
+        ("longest-fight", &extremes.longest_fights),
+    ] {
+        for (i, run) in runs.iter().enumerate() {
+            let path = args.save_dir.join(format!("{}-{}.toml", kind, i + 1));
+            let (recording, _) = Recording::record(&encounter, run.seed);
+            recording.save(&path)?;
+            println!("Saved {} ({} rounds)", path.display(), run.rounds);
+        }
+    }
+
+    Ok(())
+}
+
+fn sweep(args: SweepArgs) -> Result<()> {
+    let encounter = match &args.encounter {
+        Some(path) => Encounter::load(path)?,
+        None => level_one_encounter(6),
+    };
+
+    let monster = || -> Result<String> {
+        match &args.monster {
+            Some(name) => Ok(name.clone()),
+            None if encounter.monsters.len() == 1 => Ok(encounter.monsters[0].name.clone()),
+            None => Err(anyhow!(
+                "the encounter has several kinds of monster, pick one with --monster"
+            )),
+        }
+    };
+    let parameter = match args.param {
+        SweepParam::MonsterCount => SweepParameter::MonsterCount(monster()?),
+        SweepParam::PartyLevel => SweepParameter::PartyLevel,
+        SweepParam::MonsterAc => SweepParameter::MonsterAc(monster()?),
+        SweepParam::MonsterHp => SweepParameter::MonsterHp(monster()?),
+        SweepParam::MonsterHitBonus => SweepParameter::MonsterHitBonus(monster()?),
+    };
+    if args.step == 0 {
+        return Err(anyhow!("--step must be at least 1"));
+    }
+    let values: Vec<isize> = (args.from..=args.to).step_by(args.step).collect();
+
+    let mut iterator = sweep_iterator(&encounter, parameter, values, args.iterations)?;
+    let bar = ProgressBar::new(iterator.total_iterations() as u64);
+    for _ in iterator.by_ref() {
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+
+    if args.csv {
+        println!(
+            "value,iterations,hero_win_rate,hero_win_ci_lower,hero_win_ci_upper,average_rounds,average_rounds_std_err,decisive_rate,pyrrhic_rate,mcdm_difficulty,wizards_2014_difficulty,wizards_2024_difficulty"
+        );
+        for point in &iterator.results {
+            let stats = &point.stats;
+            let (mcdm, wizards2014, wizards2024) = sweep_point_difficulties(point)?;
+            println!(
+                "{},{},{:.5},{:.5},{:.5},{:.5},{:.5},{:.5},{:.5},{},{},{}",
+                point.value,
+                stats.iterations,
+                stats.hero_victories_perc,
+                stats.hero_victories_ci.lower,
+                stats.hero_victories_ci.upper,
+                stats.average_rounds,
+                stats.average_rounds_std_err,
+                stats.decisive_victories_perc,
+                stats.pyrrhic_victories_perc,
+                mcdm,
+                wizards2014,
+                wizards2024
+            );
+        }
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.set_header(vec![
+        iterator.parameter.description(),
+        "Player Victories".into(),
+        "Average Rounds".into(),
+        "Decisive Victories".into(),
+        "Pyrrhic Victories".into(),
+        "MCDM Difficulty".into(),
+        "Wizards Difficulty".into(),
+    ]);
+    for point in &iterator.results {
+        let stats = &point.stats;
+        let (mcdm, wizards2014, wizards2024) = sweep_point_difficulties(point)?;
+        table.add_row(vec![
+            format!("{}", point.value),
+            victories_formatted(
+                stats.hero_victories,
+                stats.hero_victories_perc,
+                &stats.hero_victories_ci,
+            ),
+            format!(
+                "{:.3} ± {:.3}",
+                stats.average_rounds, stats.average_rounds_std_err
+            ),
+            victories_formatted(
+                stats.decisive_victories,
+                stats.decisive_victories_perc,
+                &stats.decisive_victories_ci,
+            ),
+            victories_formatted(
+                stats.pyrrhic_victories,
+                stats.pyrrhic_victories_perc,
+                &stats.pyrrhic_victories_ci,
+            ),
+            mcdm,
+            format!("2014: {}\n2024: {}", wizards2014, wizards2024),
+        ]);
+    }
+
+    println!("{table}");
+
+    Ok(())
+}
+
+fn compare(args: CompareArgs) -> Result<()> {
+    let a = Encounter::load(&args.a)?;
+    let b = Encounter::load(&args.b)?;
+    let seed = args.seed.unwrap_or_else(random_seed);
+
+    let mut iterator = compare_iterator(a, b, args.iterations, seed);
+    let bar = ProgressBar::new(args.iterations as u64);
+    for _ in iterator.by_ref() {
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    let comparison = iterator
+        .comparison
+        .ok_or_else(|| anyhow!("comparison didn't finish"))?;
+
+    println!("A: {}", args.a.display());
+    println!("B: {}", args.b.display());
+    println!("Seed: {}, {} combats each\n", seed, args.iterations);
+
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Metric",
+        "A",
+        "B",
+        "Difference (A - B)",
+        "95% CI",
+        "p-value",
+        "Significant",
+    ]);
+    table.add_row(difference_row(
+        "Player Victories".into(),
+        &comparison.hero_victories,
+        true,
+    ));
+    table.add_row(difference_row(
+        "Decisive Victories".into(),
+        &comparison.decisive_victories,
+        true,
+    ));
+    table.add_row(difference_row("Draws".into(), &comparison.draws, true));
+    table.add_row(difference_row("Routs".into(), &comparison.routs, true));
+    table.add_row(difference_row("Wipes".into(), &comparison.wipes, true));
+    table.add_row(difference_row("Rounds".into(), &comparison.rounds, false));
+    let mut heroes: Vec<_> = comparison.hero_ko_rates.iter().collect();
+    heroes.sort_by(|x, y| x.0.cmp(y.0));
+    for (name, difference) in heroes {
+        table.add_row(difference_row(format!("{} K/O", name), difference, true));
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+fn calibrate(args: CalibrateArgs) -> Result<()> {
+    let seed = args.seed.unwrap_or_else(random_seed);
+    let mut iterator = calibration_iterator(args.encounters, args.iterations, args.max_level, seed);
+    let bar = ProgressBar::new(args.encounters as u64);
+    for _ in iterator.by_ref() {
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+
+    if args.csv {
+        println!(
+            "pc_levels,monster_crs,mcdm_difficulty,wizards_2014_difficulty,wizards_2024_difficulty,hero_win_rate,heroes_downed,hp_burned,resources_burned,simulated_difficulty"
+        );
+        for point in &iterator.results {
+            let outcome = &point.simulated;
+            println!(
+                "{},{},{},{:.5},{:.5},{:.5},{:.5},{}",
+                joined(&point.encounter.hero_levels()),
+                joined(&point.encounter.monster_crs()),
+                point.table_ratings.join(","),
+                outcome.win_rate,
+                outcome.heroes_downed,
+                outcome.hp_burned,
+                outcome.resources_burned,
+                String::from(outcome.difficulty)
+            );
+        }
+        return Ok(());
+    }
+
+    println!(
+        "Seed: {}, {} encounters, {} combats each\n",
+        seed, args.encounters, args.iterations
+    );
+    for calculator in TableCalculator::ALL {
+        let tab = cross_tab(&iterator.results, calculator);
+        let mut table = Table::new();
+        let mut header = vec![format!("{} Rating", calculator.description())];
+        header.extend(
+            SimulatedDifficulty::ALL
+                .iter()
+                .map(|d| format!("Simulated {}", String::from(*d))),
+        );
+        table.set_header(header);
+        for (rating, counts) in &tab.rows {
+            let total: usize = counts.iter().sum();
+            let mut row = vec![format!("{} ({})", rating, total)];
+            row.extend(counts.iter().map(|count| {
+                if total == 0 {
+                    "-".into()
+                } else {
+                    format!("{} ({:.1}%)", count, *count as f64 / total as f64 * 100.0)
+                }
+            }));
+            table.add_row(row);
+        }
+        println!("{table}");
+    }
+
+    Ok(())
+}
+
+fn simulated_formatted(outcome: &SimulatedOutcome) -> String {
+    format!(
+        "{}\nHeroes downed: {:.2}\nHP burned: {:.1}%\nResources burned: {:.1}%",
+        String::from(outcome.difficulty),
+        outcome.heroes_downed,
+        outcome.hp_burned * 100.0,
+        outcome.resources_burned * 100.0
+    )
+}
+
+fn build_encounter(args: BuildEncounterArgs) -> Result<()> {
+    let bestiary = Encounter::load(&args.monsters)?.monsters;
+    let builder = EncounterBuilder::new(
+        args.party.clone(),
+        bestiary,
+        args.rules.into(),
+        &args.difficulty,
+    )?
+    .with_max_monsters(args.max_monsters)
+    .with_max_kinds(args.max_kinds);
+
+    let candidates = builder.build(args.count);
+    if candidates.is_empty() {
+        return Err(anyhow!(
+            "no combination of up to {} monsters rates {} for this party",
+            args.max_monsters,
+            args.difficulty
+        ));
+    }
+
+    let mut table = Table::new();
+    let mut header = vec!["Monsters", "Total CR", "Rating"];
+    if args.verify.is_some() {
+        header.extend(["Simulated Difficulty", "Player Victories"]);
+    }
+    table.set_header(header);
+
+    for candidate in &candidates {
+        let mut row = vec![
+            candidate
+                .monsters
+                .iter()
+                .map(|m| format!("{} x {} (CR {})", m.count, m.name, m.challenge_rating))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            format!(
+                "{}",
+                candidate
+                    .monsters
+                    .iter()
+                    .map(|m| m.challenge_rating * m.count as f64)
+                    .sum::<f64>()
+            ),
+            candidate.rating.into(),
+        ];
+        if let Some(iterations) = args.verify {
+            let outcome = builder.verify(candidate, iterations);
+            row.push(String::from(outcome.difficulty));
+            row.push(format!("{:.1}%", outcome.win_rate * 100.0));
+        }
+        table.add_row(row);
+    }
+    println!("{table}");
+
+    Ok(())
+}
+
+fn day(args: DayArgs) -> Result<()> {
+    let day = AdventuringDay::load(&args.day)?;
+    let mut iterator = day_monte_carlo_iterator(args.iterations, day.clone());
+    let bar = ProgressBar::new(args.iterations as u64);
+    for _ in iterator.by_ref() {
+        bar.inc(1);
+    }
+    bar.finish_and_clear();
+    let stats = iterator.stats;
+
+    if let Some(name) = &day.name {
+        println!("{}\n", name);
+    }
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Iterations",
+        "Days Completed",
+        "Encounters Won",
+        "Encounters Won Histogram",
+    ]);
+    table.add_row(vec![
+        format!("{}", stats.iterations),
+        victories_formatted(
+            stats.completed_days,
+            stats.completed_days_perc,
+            &stats.completed_days_ci,
         ),
-        format!("{}", String::from(mcdm.calculate())),
         format!(
-            "2014: {}\n2024: {}",
-            String::from(wizards2014.calculate()),
-            String::from(wizards2024.calculate())
+            "{:.3} ± {:.3}",
+            stats.encounters_won.mean(),
+            stats.encounters_won.standard_error()
         ),
+        histogram_formatted(&stats.encounters_won),
     ]);
+    println!("{table}");
 
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Encounter",
+        "Reached",
+        "Won (of reached)",
+        "Average Rounds",
+        "Party HP After",
+        "Heroes Standing After",
+        "Resources Left After",
+    ]);
+    for encounter in &stats.encounters {
+        let reached = |perc: f64| format!("{:.2}%", perc * 100.0);
+        table.add_row(vec![
+            encounter.name.clone(),
+            format!(
+                "{} ({})",
+                encounter.reached,
+                reached(encounter.reached as f64 / stats.iterations.max(1) as f64)
+            ),
+            format!("{} ({})", encounter.won, reached(encounter.win_rate())),
+            format!("{:.3}", encounter.rounds.mean()),
+            format!("{:.2}", encounter.party_hp_remaining.mean()),
+            format!("{:.2}", encounter.heroes_standing.mean()),
+            format!("{:.2}", encounter.resources_remaining.mean()),
+        ]);
+    }
     println!("{table}");
 
     Ok(())
 }
 
+fn joined<T: ToString>(values: &[T]) -> String {
+    values
+        .iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn random_seed() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+fn difference_row(metric: String, difference: &PairedDifference, percent: bool) -> Vec<String> {
+    let value = |v: f64| {
+        if percent {
+            format!("{:.2}%", v * 100.0)
+        } else {
+            format!("{:.3}", v)
+        }
+    };
+    vec![
+        metric,
+        value(difference.a_mean),
+        value(difference.b_mean),
+        value(difference.difference),
+        format!(
+            "{} to {}",
+            value(difference.ci.lower),
+            value(difference.ci.upper)
+        ),
+        format!("{:.4}", difference.p_value),
+        if difference.significant() {
+            "yes".into()
+        } else {
+            "no".into()
+        },
+    ]
+}
+
+fn sweep_point_difficulties(point: &SweepPoint) -> Result<(String, String, String)> {
+    difficulties(point.encounter.hero_levels(), point.encounter.monster_crs())
+}
+
+/// MCDM, Wizards 2014 and Wizards 2024 difficulty ratings
+fn difficulties(
+    hero_levels: Vec<usize>,
+    monster_crs: Vec<f64>,
+) -> Result<(String, String, String)> {
+    let mcdm: DifficultyCalculator<MCDMDifficultyCalculator> =
+        DifficultyCalculator::new(hero_levels.clone(), monster_crs.clone());
+    let wizards2014: DifficultyCalculator<WizardDifficultyCalculator2014> =
+        DifficultyCalculator::new(hero_levels.clone(), monster_crs.clone());
+    let wizards2024: DifficultyCalculator<WizardDifficultyCalculator2024> =
+        DifficultyCalculator::new(hero_levels, monster_crs);
+
+    Ok((
+        mcdm.calculate()?.into(),
+        wizards2014.calculate()?.into(),
+        wizards2024.calculate()?.into(),
+    ))
+}
+
+fn distributions_table(stats: &CombatStats) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Metric",
+        "Mean",
+        "Min",
+        "10th",
+        "25th",
+        "Median",
+        "75th",
+        "90th",
+        "Max",
+        "Histogram",
+    ]);
+
+    for (metric, distribution) in [
+        ("Rounds", &stats.rounds),
+        ("Party HP Remaining", &stats.party_hp_remaining),
+        ("Heroes Standing", &stats.heroes_standing),
+    ] {
+        table.add_row(distribution_row(
+            metric.into(),
+            distribution,
+            histogram_formatted(distribution),
+        ));
+    }
+
+    for (metric, distributions) in [
+        ("Damage Dealt", &stats.damage_dealt),
+        ("Damage Taken", &stats.damage_taken),
+        ("Healing Done", &stats.healing_done),
+        ("Resources Spent", &stats.resources_spent),
+    ] {
+        let mut names: Vec<&String> = distributions.keys().collect();
+        names.sort();
+        for name in names {
+            table.add_row(distribution_row(
+                format!("{}: {}", metric, name),
+                &distributions[name],
+                String::new(),
+            ));
+        }
+    }
+
+    table
+}
+
+fn contributions_table(stats: &CombatStats) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Combatant",
+        "Avg Damage Dealt",
+        "Hit Rate",
+        "Crit Rate",
+        "Avg Kills",
+        "Avg Damage Taken",
+        "Avg Healing Given",
+        "Avg Healing Received",
+        "Avg Turns Incapacitated",
+        "Avg Concentration Broken",
+        "Avg Rounds Survived",
+    ]);
+
+    let mut names: Vec<&String> = stats.contributions.keys().collect();
+    names.sort();
+    for name in names {
+        let contribution = &stats.contributions[name];
+        table.add_row(vec![
+            name.clone(),
+            format!("{:.3}", contribution.average_damage_dealt()),
+            format!("{:.3}%", contribution.hit_rate() * 100.0),
+            format!("{:.3}%", contribution.crit_rate() * 100.0),
+            format!("{:.3}", contribution.average_kills()),
+            format!("{:.3}", contribution.average_damage_taken()),
+            format!("{:.3}", contribution.average_healing_given()),
+            format!("{:.3}", contribution.average_healing_received()),
+            format!("{:.3}", contribution.average_turns_incapacitated()),
+            format!("{:.3}", contribution.average_concentration_broken()),
+            format!("{:.3}", contribution.average_rounds_survived()),
+        ]);
+    }
+
+    table
+}
+
+fn distribution_row(metric: String, distribution: &Distribution, histogram: String) -> Vec<String> {
+    vec![
+        metric,
+        format!("{:.3}", distribution.mean()),
+        format!("{}", distribution.min()),
+        format!("{}", distribution.percentile(10.0)),
+        format!("{}", distribution.percentile(25.0)),
+        format!("{}", distribution.median()),
+        format!("{}", distribution.percentile(75.0)),
+        format!("{}", distribution.percentile(90.0)),
+        format!("{}", distribution.max()),
+        histogram,
+    ]
+}
+
+fn histogram_formatted(distribution: &Distribution) -> String {
+    let total = distribution.len() as f64;
+    distribution
+        .histogram(HISTOGRAM_BUCKETS)
+        .iter()
+        .map(|bucket| {
+            let perc = bucket.count as f64 / total * 100.0;
+            let label = if bucket.lower == bucket.upper {
+                format!("{}", bucket.lower)
+            } else {
+                format!("{}-{}", bucket.lower, bucket.upper)
+            };
+            format!(
+                "{:>7}: {:<20} {:.1}%",
+                label,
+                "#".repeat((perc / 5.0).round() as usize),
+                perc
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn victories_formatted(count: usize, perc: f64, ci: &ConfidenceInterval) -> String {
+    format!(
+        "{} ({:.3}%)\n95% CI: {:.3}%-{:.3}%",
+        count,
+        perc * 100.0,
+        ci.lower * 100.0,
+        ci.upper * 100.0
+    )
+}
+
 fn actor_ko_counts_formatted(counts: &HashMap<String, usize>) -> String {
     let mut parts = vec![];
     for (name, kos) in counts {
@@ -243,54 +1085,168 @@ fn actor_ko_counts_formatted(counts: &HashMap<String, usize>) -> String {
     parts.join("\n")
 }
 
+fn cause_name(cause: Cause) -> String {
+    match cause {
+        Cause::Action(action) => action_name(action),
+        Cause::Trigger(name) => name,
+    }
+}
+
 fn action_name(action: Action) -> String {
     match action {
-        Action::Attack { name, .. } => format!("Attack with {}", name).into(),
-        Action::Heal { name, .. } => format!("Heal with {}", name).into(),
-        Action::SecondWind { .. } => format!("Second wind used!"),
+        Action::Attack { name, .. } => format!("Attack with {}", name),
+        Action::Heal { name, .. } => format!("Heal with {}", name),
+        Action::SecondWind { .. } => "Second wind used!".to_string(),
+        Action::CastSpell {
+            spell,
+            slot_level: Some(level),
+        } if !spell.is_cantrip() => format!("Cast {} at level {}", spell.name, level),
+        Action::CastSpell { spell, .. } => format!("Cast {}", spell.name),
+        Action::Save { name, .. } => format!("Use {}", name),
     }
 }
 
-fn actor_name(actor: Character) -> String {
-    actor.name
+/// Name, current and max HP of everyone in the fight
+fn starting_hps(combat: &Combat) -> Vec<(String, usize, usize)> {
+    combat
+        .creatures()
+        .map(|c| (c.name.clone(), c.current_hp, c.max_hp))
+        .collect()
 }
 
-fn result_debug(result: ActionResult) -> String {
-    match result {
-        ActionResult::Attack { hit, damage } => match hit {
-            HitResult::Critical => format!("Critical for {}", damage),
-            HitResult::Hit => format!("Hit for {}", damage),
-            HitResult::Miss => format!("Miss"),
-        },
-        ActionResult::Heal { amount } => format!("Healed for {}", amount),
-        ActionResult::None => "No result".into(),
+/// One row per thing that happened to someone, with everyone's HP after
+/// it. Going down shows in the HPs.
+fn event_table(events: &[Event], mut hps: Vec<(String, usize, usize)>) -> Table {
+    let mut table = Table::new();
+    table.set_header(vec![
+        "Round", "Actor", "Action", "Target", "Result", "All Hps",
+    ]);
+    let mut round = 0;
+    let mut actor = String::new();
+    let mut action = String::new();
+    let mut events = events.iter().peekable();
+    while let Some(event) = events.next() {
+        // Damage from a hit or a failed save goes on the same row
+        let mut damage_to = |target: &str| match events.peek() {
+            Some(Event::DamageApplied {
+                target: t,
+                amount,
+                hp,
+                ..
+            }) if t == target => {
+                let amount = *amount;
+                set_hp(&mut hps, t, *hp);
+                events.next();
+                Some(amount)
+            }
+            _ => None,
+        };
+        let (target, result) = match event {
+            Event::TurnStarted {
+                round: r,
+                creature,
+                hp,
+            } => {
+                round = *r;
+                actor = creature.clone();
+                action = String::new();
+                set_hp(&mut hps, creature, *hp);
+                continue;
+            }
+            Event::ActionTaken {
+                actor: a, cause, ..
+            } => {
+                actor = a.clone();
+                action = cause_name(cause.clone());
+                continue;
+            }
+            Event::AttackHit {
+                target,
+                critical,
+                cover,
+                ..
+            } => {
+                let hit = if *critical { "Critical" } else { "Hit" };
+                let result = match damage_to(target) {
+                    Some(amount) => format!("{} for {}", hit, amount),
+                    None => hit.to_string(),
+                };
+                (target, with_cover(result, *cover))
+            }
+            Event::AttackMissed { target, cover, .. } => {
+                (target, with_cover("Miss".into(), *cover))
+            }
+            Event::Rolled {
+                creature,
+                roll: Roll::Save { ability },
+                total,
+                against: Some(dc),
+            } => {
+                let result = if total >= dc {
+                    "Saved".to_string()
+                } else {
+                    format!("Failed {:?} save", ability)
+                };
+                match damage_to(creature) {
+                    Some(amount) => (creature, format!("{}, took {}", result, amount)),
+                    None => (creature, result),
+                }
+            }
+            Event::DamageApplied {
+                target, amount, hp, ..
+            } => {
+                set_hp(&mut hps, target, *hp);
+                (target, format!("Took {}", amount))
+            }
+            Event::Healed {
+                target, amount, hp, ..
+            } => {
+                set_hp(&mut hps, target, *hp);
+                (target, format!("Healed for {}", amount))
+            }
+            Event::ConditionAdded {
+                creature,
+                condition,
+            } => (creature, condition.name()),
+            Event::ConditionRemoved {
+                creature,
+                condition,
+            } => (creature, format!("{} ends", condition.name())),
+            Event::Departed {
+                creature,
+                departure,
+            } => (creature, format!("{:?}", departure)),
+            Event::Rolled { .. }
+            | Event::ResourceSpent { .. }
+            | Event::Downed { .. }
+            | Event::RoundEnded { .. } => continue,
+        };
+        table.add_row(vec![
+            round.to_string(),
+            actor.clone(),
+            action.clone(),
+            target.clone(),
+            result,
+            hps.iter()
+                .map(|(name, hp, max)| format!("{}: {}/{}", name, hp, max))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        ]);
     }
+    table
 }
 
-fn all_hps(heroes: &Vec<Character>, villains: &Vec<Character>) -> String {
-    let mut result = vec![];
-    for hero in heroes {
-        result.push(format!(
-            "{}: {}/{}",
-            hero.name, hero.current_hp, hero.max_hp
-        ));
+/// Creatures that joined partway through start out at the HP first seen
+fn set_hp(hps: &mut Vec<(String, usize, usize)>, name: &str, hp: usize) {
+    match hps.iter_mut().find(|(n, _, _)| n == name) {
+        Some((_, current, _)) => *current = hp,
+        None => hps.push((name.to_string(), hp, hp)),
     }
-    for villain in villains {
-        result.push(format!(
-            "{}: {}/{}",
-            villain.name, villain.current_hp, villain.max_hp
-        ));
-    }
-    result.join("\n")
 }
 
-fn all_acs(heroes: &Vec<Character>, villains: &Vec<Character>) -> String {
-    let mut result = vec![];
-    for hero in heroes {
-        result.push(format!("{}: {}", hero.name, hero.ac));
-    }
-    for villain in villains {
-        result.push(format!("{}: {}", villain.name, villain.ac));
+fn with_cover(result: String, cover: Cover) -> String {
+    match cover {
+        Cover::None => result,
+        cover => format!("{} ({})", result, cover.name()),
     }
-    result.join("\n")
 }
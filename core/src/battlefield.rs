@@ -0,0 +1,413 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{Ability, DamageType},
+    Character, DicePool,
+};
+
+/// Feet per grid square
+pub const SQUARE: usize = 5;
+
+/// A square on the battle grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Square {
+    pub x: isize,
+    pub y: isize,
+}
+
+impl Square {
+    pub fn new(x: isize, y: isize) -> Self {
+        Square { x, y }
+    }
+
+    /// Distance in feet, with diagonals costing the same as straight moves
+    pub fn distance(&self, other: &Square) -> usize {
+        let squares = (self.x - other.x)
+            .unsigned_abs()
+            .max((self.y - other.y).unsigned_abs());
+        squares * SQUARE
+    }
+
+    pub fn neighbours(&self) -> impl Iterator<Item = Square> + '_ {
+        (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dy| (dx, dy)))
+            .filter(|&(dx, dy)| dx != 0 || dy != 0)
+            .map(|(dx, dy)| Square::new(self.x + dx, self.y + dy))
+    }
+}
+
+/// How far an action reaches. Only matters once creatures have positions.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Range {
+    /// Creatures within `reach` feet
+    Melee {
+        #[serde(default = "default_reach")]
+        reach: usize,
+    },
+    /// Out to `normal` feet, and with disadvantage out to `long`
+    Ranged { normal: usize, long: usize },
+}
+
+fn default_reach() -> usize {
+    SQUARE
+}
+
+impl Default for Range {
+    fn default() -> Self {
+        Range::Melee {
+            reach: default_reach(),
+        }
+    }
+}
+
+impl Range {
+    /// Without a long range, like most spells
+    pub fn within(feet: usize) -> Self {
+        Range::Ranged {
+            normal: feet,
+            long: feet,
+        }
+    }
+
+    pub fn is_melee(&self) -> bool {
+        matches!(self, Range::Melee { .. })
+    }
+
+    /// Furthest a target can be, in feet
+    pub fn max(&self) -> usize {
+        match self {
+            Range::Melee { reach } => *reach,
+            Range::Ranged { long, .. } => *long,
+        }
+    }
+
+    /// Furthest a target can be without disadvantage, in feet
+    pub fn normal(&self) -> usize {
+        match self {
+            Range::Melee { reach } => *reach,
+            Range::Ranged { normal, .. } => *normal,
+        }
+    }
+
+    /// Whether a target `distance` feet away is past normal range
+    pub fn is_long(&self, distance: usize) -> bool {
+        matches!(self, Range::Ranged { normal, .. } if distance > *normal)
+    }
+}
+
+/// Cover a target has from whatever is between it and whoever is targeting
+/// it
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Cover {
+    #[default]
+    None,
+    Half,
+    ThreeQuarters,
+    /// Can't be targeted at all
+    Total,
+}
+
+impl Cover {
+    /// Added to AC
+    pub fn ac_bonus(&self) -> isize {
+        match self {
+            Cover::Half => 2,
+            Cover::ThreeQuarters => 5,
+            Cover::None | Cover::Total => 0,
+        }
+    }
+
+    /// Added to DEX saving throws, and no others
+    pub fn save_bonus(&self, ability: Ability) -> isize {
+        match ability {
+            Ability::Dexterity => self.ac_bonus(),
+            _ => 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cover::None => "no cover",
+            Cover::Half => "half cover",
+            Cover::ThreeQuarters => "three-quarters cover",
+            Cover::Total => "total cover",
+        }
+    }
+}
+
+/// Squares of a grid with something on them. Squares giving total cover are
+/// walls nobody can move through.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Terrain {
+    /// Shown in the log when a hazard goes off
+    #[serde(default)]
+    pub name: Option<String>,
+    pub squares: Vec<Square>,
+    /// Given to creatures on the far side of it
+    #[serde(default)]
+    pub cover: Cover,
+    /// Costs double movement to enter
+    #[serde(default)]
+    pub difficult: bool,
+    #[serde(default)]
+    pub hazard: Option<Hazard>,
+    /// Most creatures that can attack someone standing here in melee in a
+    /// round, like a narrow doorway
+    #[serde(default)]
+    pub chokepoint: Option<usize>,
+}
+
+impl Terrain {
+    /// Open ground, until something is set
+    pub fn new(squares: Vec<Square>) -> Self {
+        Terrain {
+            name: None,
+            squares,
+            cover: Cover::None,
+            difficult: false,
+            hazard: None,
+            chokepoint: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("Hazard")
+    }
+}
+
+/// Damage dealt to creatures in hazardous terrain, like a fire or a pit of
+/// spikes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Hazard {
+    pub damage: DicePool,
+    #[serde(default)]
+    pub damage_type: Option<DamageType>,
+    #[serde(default)]
+    pub when: HazardTiming,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HazardTiming {
+    /// Once per turn when a creature moves in, and when it starts its turn
+    /// there
+    #[default]
+    EntryOrTurnStart,
+    Entry,
+    TurnStart,
+}
+
+impl HazardTiming {
+    pub fn on_entry(&self) -> bool {
+        matches!(self, HazardTiming::EntryOrTurnStart | HazardTiming::Entry)
+    }
+
+    pub fn on_turn_start(&self) -> bool {
+        matches!(
+            self,
+            HazardTiming::EntryOrTurnStart | HazardTiming::TurnStart
+        )
+    }
+}
+
+/// Squares a line from the centre of `from` to the centre of `to` passes
+/// through, leaving out both ends
+pub fn squares_between(from: Square, to: Square) -> Vec<Square> {
+    let (dx, dy) = ((to.x - from.x) as f64, (to.y - from.y) as f64);
+    // Enough samples to land in every square the line crosses
+    let samples = 4 * (to.x - from.x).abs().max((to.y - from.y).abs());
+    let mut squares = vec![];
+    for k in 1..samples {
+        let t = k as f64 / samples as f64;
+        let square = Square::new(
+            from.x + (dx * t).round() as isize,
+            from.y + (dy * t).round() as isize,
+        );
+        if square != from && square != to && !squares.contains(&square) {
+            squares.push(square);
+        }
+    }
+    squares
+}
+
+/// A line of a side in theater of the mind positioning, front to back
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Zone {
+    Front,
+    Back,
+    Ranged,
+}
+
+/// The frontmost zone with anyone still standing in it; once a front line
+/// collapses, the zone behind it is the new front
+pub fn front_line<'a>(creatures: impl IntoIterator<Item = &'a Character>) -> Option<Zone> {
+    creatures
+        .into_iter()
+        .filter(|creature| creature.current_hp > 0)
+        .filter_map(|creature| creature.zone)
+        .min()
+}
+
+/// Distance in feet to the closest of `targets`
+pub fn nearest(from: Square, targets: &[Square]) -> Option<usize> {
+    targets.iter().map(|target| from.distance(target)).min()
+}
+
+/// The free neighbouring square that gets closest to `targets`, if any gets
+/// closer at all. Squares in `rough` are only taken if nothing else gets
+/// closer, and ties go to the square most nearly in a straight line.
+pub fn step_toward(
+    from: Square,
+    targets: &[Square],
+    occupied: &HashSet<Square>,
+    rough: &HashSet<Square>,
+) -> Option<Square> {
+    let current = nearest(from, targets)?;
+    from.neighbours()
+        .filter(|square| !occupied.contains(square))
+        .filter(|square| nearest(*square, targets).is_some_and(|d| d < current))
+        .min_by_key(|square| {
+            (
+                rough.contains(square),
+                nearest(*square, targets),
+                straightness(*square, targets),
+            )
+        })
+}
+
+/// The free neighbouring square that gets furthest from `targets`, if any
+/// gets further at all, keeping out of `rough` squares where it can
+pub fn step_away(
+    from: Square,
+    targets: &[Square],
+    occupied: &HashSet<Square>,
+    rough: &HashSet<Square>,
+) -> Option<Square> {
+    let current = nearest(from, targets)?;
+    from.neighbours()
+        .filter(|square| !occupied.contains(square))
+        .filter(|square| nearest(*square, targets).is_some_and(|d| d > current))
+        .max_by_key(|square| (!rough.contains(square), nearest(*square, targets)))
+}
+
+/// Squared straight-line distance to the closest target
+fn straightness(from: Square, targets: &[Square]) -> Option<isize> {
+    targets
+        .iter()
+        .map(|target| (from.x - target.x).pow(2) + (from.y - target.y).pow(2))
+        .min()
+}
+
+/// How creatures are placed in an encounter; without it everyone can reach
+/// everyone
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Positioning {
+    /// A square grid. Creatures without a `position` line up side by side,
+    /// the heroes on row 0 and the monsters `distance` feet away.
+    Grid {
+        #[serde(default = "default_distance")]
+        distance: usize,
+        #[serde(default)]
+        terrain: Vec<Terrain>,
+    },
+    /// Theater of the mind: each side has a front line, a back line and a
+    /// ranged zone. Melee only reaches the enemy front line, ranged attacks
+    /// reach anywhere and actions with several targets hit a single zone.
+    /// While a front line stands, it gives the zones behind it half cover.
+    /// Creatures without a `zone` go in front if they have a melee action,
+    /// in the ranged zone if they only have ranged ones and in the back
+    /// otherwise.
+    Zones,
+}
+
+fn default_distance() -> usize {
+    30
+}
+
+#[cfg(test)]
+mod battlefield_tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        let origin = Square::new(0, 0);
+        assert_eq!(origin.distance(&Square::new(1, 1)), 5);
+        assert_eq!(origin.distance(&Square::new(-2, 6)), 30);
+        assert_eq!(origin.neighbours().count(), 8);
+        assert!(origin
+            .neighbours()
+            .all(|square| origin.distance(&square) == 5));
+    }
+
+    #[test]
+    fn test_ranges() {
+        let longbow = Range::Ranged {
+            normal: 150,
+            long: 600,
+        };
+        assert_eq!(longbow.max(), 600);
+        assert!(!longbow.is_long(150));
+        assert!(longbow.is_long(155));
+        assert_eq!(Range::default().max(), 5);
+        assert!(!Range::within(120).is_long(120));
+    }
+
+    #[test]
+    fn test_steps() {
+        let goblin = [Square::new(4, 0)];
+        let mut occupied = HashSet::from(goblin);
+        let mut rough = HashSet::new();
+        assert_eq!(
+            step_toward(Square::new(0, 0), &goblin, &occupied, &rough),
+            Some(Square::new(1, 0))
+        );
+        occupied.insert(Square::new(1, 0));
+        // Around the ally in the way
+        let step = step_toward(Square::new(0, 0), &goblin, &occupied, &rough).unwrap();
+        assert_eq!(nearest(step, &goblin), Some(15));
+        assert_eq!(
+            step_toward(Square::new(3, 0), &goblin, &occupied, &rough),
+            None
+        );
+
+        let step = step_away(Square::new(3, 0), &goblin, &occupied, &rough).unwrap();
+        assert_eq!(nearest(step, &goblin), Some(10));
+
+        // Around a fire rather than through it
+        rough.insert(Square::new(2, 0));
+        assert_eq!(
+            step_toward(
+                Square::new(3, 0),
+                &[Square::new(0, 0)],
+                &HashSet::new(),
+                &rough
+            ),
+            Some(Square::new(2, -1))
+        );
+    }
+
+    #[test]
+    fn test_squares_between() {
+        let origin = Square::new(0, 0);
+        assert!(squares_between(origin, Square::new(1, 1)).is_empty());
+        assert_eq!(
+            squares_between(origin, Square::new(3, 0)),
+            vec![Square::new(1, 0), Square::new(2, 0)]
+        );
+        assert_eq!(
+            squares_between(origin, Square::new(-2, -2)),
+            vec![Square::new(-1, -1)]
+        );
+        let crooked = squares_between(origin, Square::new(4, 1));
+        assert!(crooked.contains(&Square::new(2, 0)) || crooked.contains(&Square::new(2, 1)));
+        assert!(crooked.iter().all(|square| (0..=4).contains(&square.x)));
+    }
+}
@@ -1,62 +1,126 @@
-use crate::{Character, DicePool, InitiativeEntry};
+use serde::{Deserialize, Serialize};
 
-use super::ResourceType;
+use crate::{
+    battlefield::{self, Range, Zone},
+    Character, DicePool, InitiativeEntry,
+};
 
-#[derive(Clone, Debug, PartialEq)]
+use super::{spell::SpellEffect, Ability, ConditionType, DamageType, ResourceType, Spell};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Action {
     Attack {
         name: String,
         hit_bonus: isize,
         damage: DicePool,
+        #[serde(default)]
+        damage_type: Option<DamageType>,
+        #[serde(default)]
+        range: Range,
     },
     Heal {
         name: String,
         healing: DicePool,
+        #[serde(default)]
         required_resources: Vec<(ResourceType, usize)>,
     },
     SecondWind {
         healing: DicePool,
+        #[serde(default)]
         required_resources: Vec<(ResourceType, usize)>,
     },
+    /// Casts `spell` with a `slot_level` slot. Without one the caster picks
+    /// from whatever slots they have left.
+    CastSpell {
+        spell: Spell,
+        #[serde(default)]
+        slot_level: Option<usize>,
+    },
+    /// A saving throw forced on up to `targets` enemies, like a breath weapon
+    Save {
+        name: String,
+        ability: Ability,
+        dc: usize,
+        damage: DicePool,
+        #[serde(default)]
+        damage_type: Option<DamageType>,
+        #[serde(default)]
+        half_on_success: bool,
+        #[serde(default = "one")]
+        targets: usize,
+        #[serde(default)]
+        required_resources: Vec<(ResourceType, usize)>,
+        #[serde(default)]
+        range: Range,
+    },
+}
+
+fn one() -> usize {
+    1
 }
 
 impl Action {
+    #[allow(clippy::ptr_arg)]
     pub fn is_valid(
         &self,
         actor: &Character,
         allies: &Vec<Character>,
         enemies: &Vec<Character>,
     ) -> bool {
-        self.resources_available(actor) && self.valid_targets(actor, allies, enemies).len() > 0
+        self.resources_available(actor) && !self.valid_targets(actor, allies, enemies).is_empty()
     }
 
+    /// Living or wounded creatures the action could affect, leaving out
+    /// any out of range when positions are known. In zones melee only
+    /// reaches the enemy front line.
+    #[allow(clippy::ptr_arg)]
     pub fn valid_targets(
         &self,
         actor: &Character,
         allies: &Vec<Character>,
         enemies: &Vec<Character>,
+    ) -> Vec<InitiativeEntry> {
+        self.targets_in_reach(actor, allies, enemies, battlefield::front_line(enemies))
+    }
+
+    /// Like `valid_targets`, with the enemy front line worked out by the
+    /// caller, for when `enemies` doesn't hold all of them
+    pub(crate) fn targets_in_reach(
+        &self,
+        actor: &Character,
+        allies: &[Character],
+        enemies: &[Character],
+        front: Option<Zone>,
+    ) -> Vec<InitiativeEntry> {
+        let pool = if self.is_hostile() { enemies } else { allies };
+        let targets = self.targets_ignoring_range(actor, allies, enemies);
+        let Some(range) = self.range() else {
+            return targets;
+        };
+        targets
+            .into_iter()
+            .filter(|entry| {
+                let target = &pool[entry.index];
+                actor.can_reach(target, &range)
+                    && (!self.is_hostile()
+                        || !range.is_melee()
+                        || actor.zone.is_none()
+                        || target.zone.is_none()
+                        || target.zone == front)
+            })
+            .collect()
+    }
+
+    fn targets_ignoring_range(
+        &self,
+        actor: &Character,
+        allies: &[Character],
+        enemies: &[Character],
     ) -> Vec<InitiativeEntry> {
         match self {
-            Action::Attack { .. } => enemies
-                .iter()
-                .enumerate()
-                .filter(|(_i, c)| c.current_hp > 0)
-                .map(|(i, c)| InitiativeEntry {
-                    team: c.team,
-                    index: i,
-                    initiative: 0,
-                })
-                .collect(),
-            Action::Heal { .. } => allies
-                .iter()
-                .enumerate()
-                .filter(|(_i, c)| c.current_hp < c.max_hp)
-                .map(|(i, c)| InitiativeEntry {
-                    team: c.team,
-                    index: i,
-                    initiative: 0,
-                })
-                .collect(),
+            Action::Attack { .. } | Action::Save { .. } => living(enemies),
+            Action::Heal { .. } => wounded(allies),
             Action::SecondWind { .. } => allies
                 .iter()
                 .enumerate()
@@ -68,25 +132,149 @@ impl Action {
                     initiative: 0,
                 })
                 .collect(),
+            Action::CastSpell { spell, .. } => match spell.effect {
+                SpellEffect::Attack { .. }
+                | SpellEffect::Save { .. }
+                | SpellEffect::Condition { .. } => living(enemies),
+                SpellEffect::Heal { .. } => wounded(allies),
+                // Casting the same buff on someone again does nothing
+                SpellEffect::Buff { self_only, .. } => unaffected(allies, spell)
+                    .into_iter()
+                    .filter(|entry| !self_only || allies[entry.index] == *actor)
+                    .collect(),
+                SpellEffect::Debuff { .. } => unaffected(enemies, spell),
+            },
+        }
+    }
+
+    /// Whether the action targets enemies rather than allies
+    pub fn is_hostile(&self) -> bool {
+        match self {
+            Action::Attack { .. } | Action::Save { .. } => true,
+            Action::Heal { .. } | Action::SecondWind { .. } => false,
+            Action::CastSpell { spell, .. } => matches!(
+                spell.effect,
+                SpellEffect::Attack { .. }
+                    | SpellEffect::Save { .. }
+                    | SpellEffect::Condition { .. }
+                    | SpellEffect::Debuff { .. }
+            ),
+        }
+    }
+
+    /// How far the action reaches; healing potions and Second Wind don't
+    /// need one
+    pub fn range(&self) -> Option<Range> {
+        match self {
+            Action::Attack { range, .. } | Action::Save { range, .. } => Some(*range),
+            Action::CastSpell { spell, .. } => Some(spell.range),
+            Action::Heal { .. } | Action::SecondWind { .. } => None,
+        }
+    }
+
+    pub fn needs_concentration(&self) -> bool {
+        matches!(self, Action::CastSpell { spell, .. } if spell.concentration)
+    }
+
+    /// How many different creatures the action affects
+    pub fn target_count(&self) -> usize {
+        match self {
+            Action::CastSpell { spell, slot_level } => {
+                spell.targets_at(slot_level.unwrap_or(spell.level))
+            }
+            Action::Save { targets, .. } => *targets,
+            _ => 1,
         }
     }
 
-    fn resources_available(&self, actor: &Character) -> bool {
+    pub(crate) fn resources_available(&self, actor: &Character) -> bool {
         match self {
             Action::Heal {
                 required_resources, ..
+            }
+            | Action::SecondWind {
+                required_resources, ..
+            }
+            | Action::Save {
+                required_resources, ..
             } => required_resources
                 .iter()
                 .all(|(r_type, amount)| actor.has_resource(r_type, *amount)),
+            Action::CastSpell { spell, slot_level } => {
+                spell.castable_in_combat()
+                    && match slot_level {
+                        _ if spell.is_cantrip() => true,
+                        Some(level) => actor.has_resource(&ResourceType::SpellSlot(*level), 1),
+                        None => !actor.available_slots(spell).is_empty(),
+                    }
+            }
             _ => true,
         }
     }
 }
 
+fn living(characters: &[Character]) -> Vec<InitiativeEntry> {
+    characters
+        .iter()
+        .enumerate()
+        .filter(|(_i, c)| c.current_hp > 0)
+        .map(|(i, c)| InitiativeEntry {
+            team: c.team,
+            index: i,
+            initiative: 0,
+        })
+        .collect()
+}
+
+/// Living characters not already under `spell`
+fn unaffected(characters: &[Character], spell: &Spell) -> Vec<InitiativeEntry> {
+    let condition = ConditionType::Spell(spell.name.clone());
+    characters
+        .iter()
+        .enumerate()
+        .filter(|(_i, c)| c.current_hp > 0 && !c.has_condition(condition.clone()))
+        .map(|(i, c)| InitiativeEntry {
+            team: c.team,
+            index: i,
+            initiative: 0,
+        })
+        .collect()
+}
+
+fn wounded(characters: &[Character]) -> Vec<InitiativeEntry> {
+    characters
+        .iter()
+        .enumerate()
+        .filter(|(_i, c)| c.current_hp < c.max_hp)
+        .map(|(i, c)| InitiativeEntry {
+            team: c.team,
+            index: i,
+            initiative: 0,
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub enum ActionResult {
-    Attack { hit: HitResult, damage: usize },
-    Heal { amount: usize },
+    Attack {
+        hit: HitResult,
+        damage: usize,
+    },
+    Heal {
+        amount: usize,
+    },
+    Save {
+        saved: bool,
+        damage: usize,
+    },
+    Condition {
+        condition: ConditionType,
+        applied: bool,
+    },
+    /// Damage with no roll to hit or save, like ongoing damage
+    Damage {
+        amount: usize,
+    },
     None,
 }
 
@@ -1,10 +1,21 @@
 use std::{collections::HashMap, hash::Hash};
 
-use crate::{Action, ActionResult, DicePool, HitResult, Team};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    battlefield::{Cover, Range, Square, Zone},
+    Action, ActionResult, AdvantageType, DicePool, HitResult, Team,
+};
 
 use super::{
-    conditions::{ConditionType, Effect},
-    Condition,
+    conditions::{Concentration, ConditionType, Effect},
+    event::{record_roll, Event, Roll},
+    feature::Feature,
+    legendary::LegendaryActions,
+    morale::{Departure, Morale},
+    spell::{Spell, SpellEffect, Spellcasting},
+    trigger::{Trigger, TurnPoint},
+    Condition, DamageType,
 };
 
 #[derive(Clone, Debug, PartialEq)]
@@ -13,12 +24,62 @@ pub struct Character {
     pub max_hp: usize,
     pub current_hp: usize,
     pub ac: usize,
+    /// Walking speed in feet, before any effects
+    pub speed: usize,
     pub actions: Vec<Action>,
     pub team: Team,
+    /// Creatures in other factions are its enemies unless allied
+    pub faction: String,
     pub initiative_bonus: isize,
+    pub level: usize,
+    pub challenge_rating: f64,
+    /// Rolled for each hit die spent on a short rest, CON modifier included
+    pub hit_die: Option<DicePool>,
+    pub saves: HashMap<Ability, isize>,
+    pub spellcasting: Option<Spellcasting>,
+    /// At most one at a time; casting another concentration spell ends it
+    pub concentration: Option<Concentration>,
+    pub features: Vec<Feature>,
+    /// Refilled at the start of the creature's turn
+    pub legendary_actions: Option<LegendaryActions>,
+    /// Taken on initiative count 20 each round while the creature is alive
+    pub lair_actions: Vec<Action>,
+    pub triggers: Vec<Trigger>,
+    /// Leaves the fight at the end of this round, like a summon running out
+    pub leaves_after_round: Option<usize>,
+    /// Leaves the fight when its summoner stops concentrating on the spell
+    pub summoned_with: Option<Concentration>,
+    /// Where it stands, when the fight is on a grid
+    pub position: Option<Square>,
+    /// Its line, when the fight is in zones
+    pub zone: Option<Zone>,
+    /// Caught off guard when the fight starts
+    pub surprised: bool,
+    /// DEX score, for breaking initiative ties
+    pub dexterity: Option<usize>,
+    /// Creatures sharing a group roll together under group initiative
+    pub group: Option<String>,
+    pub morale: Option<Morale>,
+    /// Out of the fight without going down
+    pub departed: Option<Departure>,
+    /// Given out by the combat it joins; its tally is kept under this
+    pub(crate) id: usize,
 
     resources: Resources,
     active_conditions: Vec<Condition>,
+    turn: TurnState,
+    legendary_actions_left: usize,
+    /// Since the start of its last turn, for regeneration
+    damage_types_taken: Vec<DamageType>,
+    morale_checked: bool,
+}
+
+/// What the character has done so far this turn
+#[derive(Clone, Debug, Default, PartialEq)]
+struct TurnState {
+    /// An ally is next to the target, for Sneak Attack
+    ally_nearby: bool,
+    sneak_attack_used: bool,
 }
 
 impl Character {
@@ -34,85 +95,601 @@ impl Character {
             max_hp,
             current_hp: max_hp,
             ac,
+            speed: 30,
             actions: vec![],
             team,
+            faction: team.faction().to_string(),
             initiative_bonus,
+            level: 1,
+            challenge_rating: 0.0,
+            hit_die: None,
+            saves: HashMap::new(),
+            spellcasting: None,
+            concentration: None,
+            features: vec![],
+            legendary_actions: None,
+            lair_actions: vec![],
+            triggers: vec![],
+            leaves_after_round: None,
+            summoned_with: None,
+            position: None,
+            zone: None,
+            surprised: false,
+            dexterity: None,
+            group: None,
+            morale: None,
+            departed: None,
+            id: 0,
             resources: Resources::new(),
             active_conditions: vec![],
+            turn: TurnState::default(),
+            legendary_actions_left: 0,
+            damage_types_taken: vec![],
+            morale_checked: false,
         }
     }
 
     pub fn take_action(&mut self, target: &mut Character, action: &Action) -> ActionResult {
+        self.take_action_with_cover(target, action, Cover::None)
+    }
+
+    /// Takes `action` against a target behind `cover`, which adds to its AC
+    /// and DEX saves
+    pub fn take_action_with_cover(
+        &mut self,
+        target: &mut Character,
+        action: &Action,
+        cover: Cover,
+    ) -> ActionResult {
+        let action = self.resolve_slot(action);
+        if !self.pay_for(&action) {
+            return match action {
+                Action::Heal { .. } | Action::SecondWind { .. } => ActionResult::Heal { amount: 0 },
+                _ => ActionResult::None,
+            };
+        }
+        self.apply_action(target, &action, cover)
+    }
+
+    /// Spends whatever `action` costs. Returns false if the character can't
+    /// afford it.
+    pub fn pay_for(&mut self, action: &Action) -> bool {
+        match action {
+            Action::Attack { .. } => true,
+            Action::Heal {
+                required_resources, ..
+            }
+            | Action::SecondWind {
+                required_resources, ..
+            }
+            | Action::Save {
+                required_resources, ..
+            } => required_resources
+                .iter()
+                .all(|(resource, amount)| self.spend_resource(resource.clone(), *amount).is_ok()),
+            Action::CastSpell { spell, slot_level } => {
+                let paid = match slot_level {
+                    _ if spell.is_cantrip() => true,
+                    Some(level) => self
+                        .spend_resource(ResourceType::SpellSlot(*level), 1)
+                        .is_ok(),
+                    None => false,
+                };
+                if paid && spell.concentration {
+                    self.concentration = Some(Concentration {
+                        caster: self.name.clone(),
+                        spell: spell.name.clone(),
+                    });
+                }
+                paid
+            }
+        }
+    }
+
+    /// Resolves an already paid-for action against one target behind
+    /// `cover`. Actions with several targets are paid for once and applied
+    /// to each.
+    pub fn apply_action(
+        &mut self,
+        target: &mut Character,
+        action: &Action,
+        cover: Cover,
+    ) -> ActionResult {
         match action {
             Action::Attack {
                 name: _,
                 hit_bonus,
                 damage,
-            } => {
-                let attack_roll = DicePool::d20().add_modifier(*hit_bonus).roll();
-                if attack_roll == 20 {
-                    let damage = damage.critical_hit();
-                    let damage = damage.roll();
-                    // damage cannot be negative
-                    let damage: usize = if damage > 0 { damage as usize } else { 0 };
-                    if damage > target.current_hp {
-                        target.current_hp = 0;
-                    } else {
-                        target.current_hp -= damage;
-                    }
-                    ActionResult::Attack {
-                        hit: HitResult::Critical,
-                        damage: damage,
-                    }
-                } else if attack_roll >= target.ac as isize {
-                    let damage = damage.roll();
-                    // damage cannot be negative
-                    let damage: usize = if damage > 0 { damage as usize } else { 0 };
-                    if damage > target.current_hp {
-                        target.current_hp = 0;
+                damage_type,
+                range,
+            } => self.attack_roll(target, *hit_bonus, damage, *damage_type, range, cover, true),
+            Action::Heal { healing, .. } | Action::SecondWind { healing, .. } => {
+                ActionResult::Heal {
+                    amount: target.heal(healing.roll().max(0) as usize),
+                }
+            }
+            Action::CastSpell { spell, slot_level } => {
+                self.cast(target, spell, slot_level.unwrap_or(spell.level), cover)
+            }
+            Action::Save {
+                ability,
+                dc,
+                damage,
+                damage_type,
+                half_on_success,
+                ..
+            } => forced_save(
+                target,
+                *ability,
+                *dc,
+                damage,
+                *damage_type,
+                *half_on_success,
+                cover,
+            ),
+        }
+    }
+
+    /// Weapon attacks can also carry Sneak Attack and Divine Smite
+    #[allow(clippy::too_many_arguments)]
+    fn attack_roll(
+        &mut self,
+        target: &mut Character,
+        hit_bonus: isize,
+        damage: &DicePool,
+        damage_type: Option<DamageType>,
+        range: &Range,
+        cover: Cover,
+        weapon: bool,
+    ) -> ActionResult {
+        let advantage = self.attack_advantage_at(target, range);
+        let natural_roll = DicePool {
+            advantage_type: advantage.clone(),
+            ..DicePool::d20()
+        }
+        .roll();
+        // Attacking gives away a hidden creature
+        self.active_conditions
+            .retain(|condition| condition.condition != ConditionType::Hidden);
+        let attack_roll = natural_roll
+            + hit_bonus
+            + self.effect_roll(|effect| match effect {
+                Effect::AttackRolls { dice } => Some(dice),
+                _ => None,
+            });
+
+        let critical = natural_roll == 20;
+        let armor_class = target.armor_class() as isize + cover.ac_bonus();
+        record_roll(|| Event::Rolled {
+            creature: self.name.clone(),
+            roll: Roll::Attack,
+            total: attack_roll,
+            against: Some(armor_class),
+        });
+        if !critical && (natural_roll == 1 || attack_roll < armor_class) {
+            return ActionResult::Attack {
+                hit: HitResult::Miss,
+                damage: 0,
+            };
+        }
+
+        let melee_weapon = weapon && range.is_melee();
+        let mut damage = self.damage_against(target, damage, melee_weapon);
+        if weapon {
+            damage = self.weapon_riders(
+                damage,
+                advantage == AdvantageType::Advantage,
+                critical,
+                melee_weapon,
+            );
+        }
+        if critical {
+            ActionResult::Attack {
+                hit: HitResult::Critical,
+                damage: target.take_damage_of(damage.critical_hit().roll(), damage_type),
+            }
+        } else {
+            ActionResult::Attack {
+                hit: HitResult::Hit,
+                damage: target.take_damage_of(damage.roll(), damage_type),
+            }
+        }
+    }
+
+    /// Advantage and disadvantage cancel out, however many sources of each
+    pub fn attack_advantage(&self, target: &Character) -> AdvantageType {
+        self.attack_advantage_at(target, &Range::default())
+    }
+
+    /// As `attack_advantage`, with disadvantage past the normal range too
+    pub fn attack_advantage_at(&self, target: &Character, range: &Range) -> AdvantageType {
+        let advantage = self
+            .active_effects()
+            .any(|e| *e == Effect::AdvantageOnAttacks)
+            || target
+                .active_effects()
+                .any(|e| *e == Effect::AttackersHaveAdvantage);
+        let disadvantage = self
+            .active_effects()
+            .any(|e| *e == Effect::DisadvantageOnAttacks)
+            || self
+                .distance_to(target)
+                .is_some_and(|distance| range.is_long(distance));
+        match (advantage, disadvantage) {
+            (true, false) => AdvantageType::Advantage,
+            (false, true) => AdvantageType::Disadvantage,
+            _ => AdvantageType::None,
+        }
+    }
+
+    /// Adds Sneak Attack and Divine Smite dice to a weapon hit, using them
+    /// up as it goes. Smiting takes a melee weapon.
+    fn weapon_riders(
+        &mut self,
+        mut damage: DicePool,
+        advantage: bool,
+        critical: bool,
+        melee: bool,
+    ) -> DicePool {
+        for feature in self.features.clone() {
+            match feature {
+                Feature::SneakAttack { dice }
+                    if !self.turn.sneak_attack_used && (advantage || self.turn.ally_nearby) =>
+                {
+                    self.turn.sneak_attack_used = true;
+                    damage = damage.add_pool(&dice, 1);
+                }
+                Feature::DivineSmite if melee => {
+                    let slots: Vec<usize> = (1..=9)
+                        .filter(|level| self.has_resource(&ResourceType::SpellSlot(*level), 1))
+                        .collect();
+                    let slot = if critical {
+                        slots.last()
                     } else {
-                        target.current_hp -= damage;
-                    }
-                    ActionResult::Attack {
-                        hit: HitResult::Hit,
-                        damage: damage,
+                        slots.first()
+                    };
+                    if let Some(level) = slot.copied() {
+                        self.spend_resource(ResourceType::SpellSlot(level), 1)
+                            .expect("slot was just checked");
+                        damage = damage.add_pool(&Feature::smite_dice(level), 1);
                     }
+                }
+                _ => {}
+            }
+        }
+        damage
+    }
+
+    /// Weapon or spell damage plus any extra dice effects add to a hit on
+    /// `target`
+    fn damage_against(
+        &self,
+        target: &Character,
+        damage: &DicePool,
+        melee_weapon: bool,
+    ) -> DicePool {
+        let own = self.active_effects().filter_map(|effect| match effect {
+            Effect::DamageOnHit { dice } => Some(dice),
+            Effect::MeleeWeaponDamage { dice } if melee_weapon => Some(dice),
+            _ => None,
+        });
+        let from_target = target
+            .active_conditions
+            .iter()
+            .filter(|condition| condition.source.as_ref() == Some(&self.name))
+            .flat_map(|condition| &condition.effects)
+            .filter_map(|effect| match effect {
+                Effect::DamageFromSource { dice } => Some(dice),
+                _ => None,
+            });
+        own.chain(from_target)
+            .fold(damage.clone(), |damage, dice| damage.add_pool(dice, 1))
+    }
+
+    fn cast(
+        &mut self,
+        target: &mut Character,
+        spell: &Spell,
+        slot_level: usize,
+        cover: Cover,
+    ) -> ActionResult {
+        let spellcasting = self.spellcasting.clone().unwrap_or_default();
+        let dice = spell.dice_at(slot_level, self.level);
+        match &spell.effect {
+            SpellEffect::Attack { add_modifier, .. } => {
+                let dice = if *add_modifier {
+                    dice.add_modifier(spellcasting.modifier)
+                } else {
+                    dice
+                };
+                self.attack_roll(
+                    target,
+                    spellcasting.attack_bonus,
+                    &dice,
+                    spell.damage_type,
+                    &spell.range,
+                    cover,
+                    false,
+                )
+            }
+            SpellEffect::Save {
+                ability,
+                half_on_success,
+                ..
+            } => forced_save(
+                target,
+                *ability,
+                spellcasting.save_dc,
+                &dice,
+                spell.damage_type,
+                *half_on_success,
+                cover,
+            ),
+            SpellEffect::Heal { add_modifier, .. } => {
+                let dice = if *add_modifier {
+                    dice.add_modifier(spellcasting.modifier)
                 } else {
-                    ActionResult::Attack {
-                        hit: HitResult::Miss,
-                        damage: 0,
+                    dice
+                };
+                ActionResult::Heal {
+                    amount: target.heal(dice.roll().max(0) as usize),
+                }
+            }
+            SpellEffect::Condition {
+                condition,
+                save,
+                rounds,
+            } => {
+                let saved = save.is_some_and(|ability| {
+                    target.saving_throw_with_cover(ability, spellcasting.save_dc, cover)
+                });
+                if !saved {
+                    let mut applied = self.spell_condition(spell, condition.clone(), *rounds);
+                    if let Some(ability) = save {
+                        applied = applied.with_save_ends(*ability, spellcasting.save_dc);
                     }
+                    target.add_condition(applied);
+                }
+                ActionResult::Condition {
+                    condition: condition.clone(),
+                    applied: !saved,
                 }
             }
-            Action::Heal {
-                healing,
-                required_resources,
-                ..
+            SpellEffect::Buff {
+                effects, rounds, ..
+            } => {
+                let condition = ConditionType::Spell(spell.name.clone());
+                target.add_condition(
+                    self.spell_condition(spell, condition.clone(), *rounds)
+                        .with_effects(effects.clone()),
+                );
+                ActionResult::Condition {
+                    condition,
+                    applied: true,
+                }
             }
-            | Action::SecondWind {
-                healing,
-                required_resources,
-                ..
+            SpellEffect::Debuff {
+                effects,
+                save,
+                rounds,
             } => {
-                let resources_spent = required_resources.iter().all(|(resource, amount)| {
-                    self.spend_resource(resource.clone(), *amount).is_ok()
+                let condition = ConditionType::Spell(spell.name.clone());
+                let saved = save.is_some_and(|ability| {
+                    target.saving_throw_with_cover(ability, spellcasting.save_dc, cover)
                 });
-
-                let healing = if resources_spent {
-                    healing.roll() as usize
-                } else {
-                    0
-                };
-                target.current_hp += healing;
-                if target.current_hp > target.max_hp {
-                    target.current_hp = target.max_hp;
+                if !saved {
+                    target.add_condition(
+                        self.spell_condition(spell, condition.clone(), *rounds)
+                            .with_effects(effects.clone()),
+                    );
+                }
+                ActionResult::Condition {
+                    condition,
+                    applied: !saved,
                 }
-
-                ActionResult::Heal { amount: healing }
             }
         }
     }
 
+    /// A condition imposed by this character's spell, tied to their
+    /// concentration if the spell needs it
+    fn spell_condition(&self, spell: &Spell, condition: ConditionType, rounds: usize) -> Condition {
+        let condition = Condition::new(condition, rounds.into()).with_source(self.name.clone());
+        if spell.concentration {
+            condition.with_concentration(Concentration {
+                caster: self.name.clone(),
+                spell: spell.name.clone(),
+            })
+        } else {
+            condition
+        }
+    }
+
+    /// Picks the slot for a spell cast without one: the lowest available.
+    /// Cantrips and other actions are returned unchanged.
+    pub fn resolve_slot(&self, action: &Action) -> Action {
+        match action {
+            Action::CastSpell {
+                spell,
+                slot_level: None,
+            } if !spell.is_cantrip() => Action::CastSpell {
+                spell: spell.clone(),
+                slot_level: self.available_slots(spell).first().copied(),
+            },
+            _ => action.clone(),
+        }
+    }
+
+    /// Slot levels the character could cast `spell` with right now
+    pub fn available_slots(&self, spell: &Spell) -> Vec<usize> {
+        (spell.level..=9)
+            .filter(|level| self.has_resource(&ResourceType::SpellSlot(*level), 1))
+            .collect()
+    }
+
+    /// Applies damage, returning how much was dealt. Negative rolls deal none.
+    /// Damage calls for a concentration save, and going down ends
+    /// concentration outright.
+    pub fn take_damage(&mut self, damage: isize) -> usize {
+        self.take_damage_of(damage, None)
+    }
+
+    pub fn take_damage_of(&mut self, damage: isize, damage_type: Option<DamageType>) -> usize {
+        if let Some(damage_type) = damage_type.filter(|_| damage > 0) {
+            self.damage_types_taken.push(damage_type);
+        }
+        let mut damage = damage.max(0) as usize;
+        let resisted = damage_type.is_some_and(|damage_type| {
+            self.active_effects().any(|effect| match effect {
+                Effect::Resistance { damage_types } => damage_types.contains(&damage_type),
+                _ => false,
+            })
+        });
+        if resisted {
+            damage /= 2;
+        }
+        self.current_hp = self.current_hp.saturating_sub(damage);
+        if self.concentration.is_some()
+            && damage > 0
+            && (self.current_hp == 0 || !self.concentration_save(damage))
+        {
+            self.concentration = None;
+        }
+        damage
+    }
+
+    /// CON save against DC 10 or half the damage, whichever is higher
+    pub fn concentration_save(&mut self, damage: usize) -> bool {
+        self.saving_throw(Ability::Constitution, (damage / 2).max(10))
+    }
+
+    /// Heals up to max HP. Returns the HP actually restored, so overhealing
+    /// doesn't count.
+    pub fn heal(&mut self, amount: usize) -> usize {
+        let before = self.current_hp;
+        self.current_hp = (self.current_hp + amount).min(self.max_hp);
+        self.current_hp - before
+    }
+
+    /// A failed save can still succeed by spending a legendary resistance
+    pub fn saving_throw(&mut self, ability: Ability, dc: usize) -> bool {
+        self.saving_throw_with_cover(ability, dc, Cover::None)
+    }
+
+    pub fn saving_throw_with_cover(&mut self, ability: Ability, dc: usize, cover: Cover) -> bool {
+        let bonus = self.saves.get(&ability).copied().unwrap_or(0) + cover.save_bonus(ability);
+        let roll = DicePool::d20().add_modifier(bonus).roll()
+            + self.effect_roll(|effect| match effect {
+                Effect::SavingThrows { dice } => Some(dice),
+                _ => None,
+            });
+        record_roll(|| Event::Rolled {
+            creature: self.name.clone(),
+            roll: Roll::Save { ability },
+            total: roll,
+            against: Some(dc as isize),
+        });
+        roll >= dc as isize
+            || (self.features.contains(&Feature::LegendaryResistance)
+                && self
+                    .spend_resource(
+                        ResourceType::Feature(Feature::LEGENDARY_RESISTANCE.into()),
+                        1,
+                    )
+                    .is_ok())
+    }
+
+    /// AC including bonuses like Shield of Faith's
+    pub fn armor_class(&self) -> usize {
+        let bonus: isize = self
+            .active_effects()
+            .map(|effect| match effect {
+                Effect::ArmorClass { bonus } => *bonus,
+                _ => 0,
+            })
+            .sum();
+        (self.ac as isize + bonus).max(0) as usize
+    }
+
+    /// Walking speed after any effects, never below zero
+    pub fn current_speed(&self) -> usize {
+        let change: isize = self
+            .active_effects()
+            .map(|effect| match effect {
+                Effect::Speed { change } => *change,
+                _ => 0,
+            })
+            .sum();
+        (self.speed as isize + change).max(0) as usize
+    }
+
+    /// Distance in feet, if both are on the grid
+    pub fn distance_to(&self, other: &Character) -> Option<usize> {
+        Some(self.position?.distance(&other.position?))
+    }
+
+    /// Anyone is in reach of a creature off the grid
+    pub fn can_reach(&self, other: &Character, range: &Range) -> bool {
+        self.distance_to(other)
+            .is_none_or(|distance| distance <= range.max())
+    }
+
+    fn active_effects(&self) -> impl Iterator<Item = &Effect> {
+        self.active_conditions
+            .iter()
+            .flat_map(|condition| &condition.effects)
+    }
+
+    /// Rolls every effect dice pool `dice_of` picks out, e.g. Bless's d4 on
+    /// attack rolls
+    fn effect_roll(&self, dice_of: impl Fn(&Effect) -> Option<&DicePool>) -> isize {
+        self.active_effects()
+            .filter_map(dice_of)
+            .map(|dice| dice.roll())
+            .sum()
+    }
+
+    pub fn with_save(mut self, ability: Ability, bonus: isize) -> Self {
+        self.saves.insert(ability, bonus);
+        self
+    }
+
+    /// Makes the character a caster, with the slots their caster type gets
+    /// at their level; set the level first
+    pub fn with_spellcasting(mut self, spellcasting: Spellcasting) -> Self {
+        for (slot_level, slots) in spellcasting.caster.spell_slots(self.level) {
+            let slot = ResourceType::SpellSlot(slot_level);
+            self.add_resource(slot.clone(), slots);
+            self.set_recharge(
+                slot,
+                Recharge {
+                    on: spellcasting.caster.slot_recharge(),
+                    dice: None,
+                    roll: None,
+                },
+            );
+        }
+        self.spellcasting = Some(spellcasting);
+        self
+    }
+
+    pub fn with_level(mut self, level: usize) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn with_challenge_rating(mut self, challenge_rating: f64) -> Self {
+        self.challenge_rating = challenge_rating;
+        self
+    }
+
+    /// Gives the character one hit die per level; set the level first
+    pub fn with_hit_die(mut self, hit_die: DicePool) -> Self {
+        self.hit_die = Some(hit_die);
+        self.add_resource(ResourceType::HitDice, self.level);
+        self
+    }
+
     pub fn add_action(&mut self, action: Action) {
         self.actions.push(action);
     }
@@ -122,28 +699,125 @@ impl Character {
         self
     }
 
+    #[allow(clippy::ptr_arg)]
     pub fn valid_actions(&self, allies: &Vec<Character>, enemies: &Vec<Character>) -> Vec<Action> {
+        self.affordable_actions()
+            .into_iter()
+            .filter(|a| a.is_valid(self, allies, enemies))
+            .collect()
+    }
+
+    /// Actions the character can pay for, whether or not there's anyone
+    /// to use them on
+    pub fn affordable_actions(&self) -> Vec<Action> {
         // If any conditions on the Actor prevent them from taking actions,
         // short-circuit action selection
-        if self.active_conditions.iter().any(|con| {
-            con.effects
-                .iter()
-                .any(|eff| eff == &Effect::CantTakeActions)
-        }) {
+        if self.is_incapacitated() {
             return vec![];
         }
 
         self.actions
             .iter()
-            .filter(|a| a.is_valid(self, allies, enemies))
-            .cloned()
+            .flat_map(|action| self.castable_variants(action))
+            .filter(|a| a.resources_available(self))
             .collect()
     }
 
+    /// Name of the creature it fights for while charmed, the most recent
+    /// one if several
+    pub fn charmed_by(&self) -> Option<&str> {
+        self.active_conditions
+            .iter()
+            .rev()
+            .filter(|con| con.effects.contains(&Effect::SwitchesSides))
+            .find_map(|con| con.source.as_deref())
+    }
+
+    /// A spell without a fixed slot level can be cast with any slot the
+    /// character has left, so each of those is a separate choice
+    fn castable_variants(&self, action: &Action) -> Vec<Action> {
+        match action {
+            Action::CastSpell {
+                spell,
+                slot_level: None,
+            } if !spell.is_cantrip() => self
+                .available_slots(spell)
+                .into_iter()
+                .map(|level| Action::CastSpell {
+                    spell: spell.clone(),
+                    slot_level: Some(level),
+                })
+                .collect(),
+            _ => vec![action.clone()],
+        }
+    }
+
+    pub fn is_incapacitated(&self) -> bool {
+        self.active_conditions.iter().any(|con| {
+            con.effects
+                .iter()
+                .any(|eff| eff == &Effect::CantTakeActions)
+        })
+    }
+
     pub fn roll_initiative(&self) -> isize {
         DicePool::d20().add_modifier(self.initiative_bonus).roll()
     }
 
+    /// Still up and in the fight
+    pub fn is_standing(&self) -> bool {
+        self.current_hp > 0 && self.departed.is_none()
+    }
+
+    /// Makes its one morale save, if it has morale and hasn't already.
+    /// Failing it takes the creature out of the fight. Legendary
+    /// resistance doesn't help.
+    pub fn check_morale(&mut self) -> Option<Departure> {
+        let morale = self.morale.as_ref().filter(|_| !self.morale_checked)?;
+        self.morale_checked = true;
+        let bonus = self.saves.get(&morale.ability).copied().unwrap_or(0);
+        let roll = DicePool::d20().add_modifier(bonus).roll();
+        record_roll(|| Event::Rolled {
+            creature: self.name.clone(),
+            roll: Roll::Morale {
+                ability: morale.ability,
+            },
+            total: roll,
+            against: Some(morale.dc as isize),
+        });
+        if roll >= morale.dc as isize {
+            return None;
+        }
+        let departure = if morale.surrender {
+            Departure::Surrendered
+        } else {
+            Departure::Fled
+        };
+        self.depart(departure);
+        Some(departure)
+    }
+
+    /// Leaves the fight; anyone fleeing or escaping leaves the battlefield
+    pub fn depart(&mut self, departure: Departure) {
+        self.departed = Some(departure);
+        if departure != Departure::Surrendered {
+            self.position = None;
+        }
+    }
+
+    /// Back in the fight, with its nerve restored, e.g. for the next
+    /// encounter of the day
+    pub fn rally(&mut self) {
+        self.departed = None;
+        self.morale_checked = false;
+    }
+
+    /// Estimated from the initiative bonus when it isn't given
+    pub fn dexterity_score(&self) -> usize {
+        self.dexterity
+            .unwrap_or_else(|| (10 + 2 * self.initiative_bonus).max(1) as usize)
+    }
+
     pub fn add_resource(&mut self, resource_type: ResourceType, max: usize) {
         self.resources.add_max(resource_type, max);
     }
@@ -155,6 +829,40 @@ impl Character {
         self
     }
 
+    pub fn set_recharge(&mut self, resource_type: ResourceType, recharge: Recharge) {
+        self.resources.set_recharge(resource_type, recharge);
+    }
+
+    /// Recharges short rest resources, then spends hit dice while the
+    /// character is missing at least a die's average worth of HP
+    pub fn short_rest(&mut self) {
+        self.resources.short_rest();
+
+        let Some(hit_die) = self.hit_die.clone() else {
+            return;
+        };
+        while (self.max_hp - self.current_hp) as f64 >= hit_die.average()
+            && self.spend_resource(ResourceType::HitDice, 1).is_ok()
+        {
+            let healing = hit_die.roll().max(0) as usize;
+            self.current_hp = (self.current_hp + healing).min(self.max_hp);
+        }
+    }
+
+    /// Full HP, half the hit dice and every short and long rest resource
+    /// back, and any lingering conditions end
+    pub fn long_rest(&mut self) {
+        self.resources.long_rest();
+        self.current_hp = self.max_hp;
+        self.active_conditions.clear();
+        self.concentration = None;
+    }
+
+    /// Recharges "per day" resources
+    pub fn dawn(&mut self) {
+        self.resources.rest(Rest::Dawn);
+    }
+
     pub fn spend_resource(
         &mut self,
         resource_type: ResourceType,
@@ -163,34 +871,275 @@ impl Character {
         self.resources.spend(resource_type, amount)
     }
 
+    /// Total units of every resource the character has left
+    pub fn resources_remaining(&self) -> usize {
+        self.resources.total()
+    }
+
+    /// Total units of every resource the character has when fully rested,
+    /// spell slots included
+    pub fn resources_max(&self) -> usize {
+        self.resources.max_total()
+    }
+
     pub fn has_resource(&self, resource_type: &ResourceType, amount: usize) -> bool {
         let resource = self.resources.get(resource_type);
         resource >= amount
     }
 
+    /// Conditions don't stack; a new one replaces any of the same type.
+    /// Incapacitating conditions end the character's concentration.
     pub fn add_condition(&mut self, condition: Condition) {
+        if condition.effects.contains(&Effect::CantTakeActions) {
+            self.concentration = None;
+        }
+        self.active_conditions
+            .retain(|existing| existing.condition != condition.condition);
         self.active_conditions.push(condition);
     }
 
+    pub fn conditions(&self) -> impl Iterator<Item = &ConditionType> {
+        self.active_conditions
+            .iter()
+            .map(|condition| &condition.condition)
+    }
+
+    /// Concentrations keeping this character's conditions going
+    pub fn linked_concentrations(&self) -> impl Iterator<Item = &Concentration> {
+        self.active_conditions
+            .iter()
+            .filter_map(|condition| condition.concentration.as_ref())
+    }
+
+    /// Ends every condition that `concentration` was keeping going
+    pub fn end_linked_conditions(&mut self, concentration: &Concentration) {
+        self.active_conditions
+            .retain(|condition| condition.concentration.as_ref() != Some(concentration));
+    }
+
     pub fn has_condition(&self, condition: ConditionType) -> bool {
         self.active_conditions
             .iter()
             .any(|con| con.condition == condition)
     }
 
+    /// Whether an ally stands next to whoever the character attacks next.
+    /// Positioned fights set this per target; otherwise `start_turn` sets
+    /// it once for the turn.
+    pub fn set_ally_nearby(&mut self, ally_nearby: bool) {
+        self.turn.ally_nearby = ally_nearby;
+    }
+
+    pub fn with_feature(mut self, feature: Feature) -> Self {
+        self.features.push(feature);
+        self
+    }
+
+    /// Resets what the character has done this turn and uses whatever they
+    /// do before acting: a bonus action feature and Reckless Attack.
+    /// `ally_nearby` decides Sneak Attack without advantage. Returns the
+    /// number of actions the character gets, two with Action Surge.
+    pub fn start_turn(&mut self, ally_nearby: bool) -> usize {
+        self.turn = TurnState {
+            ally_nearby,
+            ..TurnState::default()
+        };
+        self.resources.start_of_turn();
+        self.legendary_actions_left = self
+            .legendary_actions
+            .as_ref()
+            .map_or(0, |legendary| legendary.per_round);
+        if self.is_incapacitated() {
+            return 1;
+        }
+
+        let mut bonus_action_used = false;
+        let mut actions = 1;
+        for feature in self.features.clone() {
+            match feature {
+                Feature::Rage { damage }
+                    if !bonus_action_used
+                        && !self.has_condition(ConditionType::Raging)
+                        && self
+                            .spend_resource(ResourceType::Feature(Feature::RAGE.into()), 1)
+                            .is_ok() =>
+                {
+                    bonus_action_used = true;
+                    self.add_condition(
+                        Condition::new(ConditionType::Raging, 10.into()).with_effects(vec![
+                            Effect::MeleeWeaponDamage {
+                                dice: DicePool::new().add_modifier(damage),
+                            },
+                        ]),
+                    );
+                }
+                Feature::CunningAction if !bonus_action_used => {
+                    bonus_action_used = true;
+                    self.add_condition(Condition::new(ConditionType::Hidden, 1.into()));
+                }
+                Feature::RecklessAttack => {
+                    // Lasts through the end of the next turn, when it's renewed
+                    self.add_condition(Condition::new(ConditionType::Reckless, 2.into()));
+                }
+                Feature::ActionSurge
+                    if self
+                        .spend_resource(ResourceType::Feature(Feature::ACTION_SURGE.into()), 1)
+                        .is_ok() =>
+                {
+                    actions += 1;
+                }
+                _ => {}
+            }
+        }
+        actions
+    }
+
+    /// Regains hit points from regeneration, unless it's down or took damage
+    /// that stops it since the start of its last turn. Returns the amount
+    /// rolled if it regenerated.
+    pub fn regenerate(&mut self) -> Option<usize> {
+        let taken = std::mem::take(&mut self.damage_types_taken);
+        let healing = self.triggers.iter().find_map(|trigger| match trigger {
+            Trigger::Regeneration {
+                healing,
+                suppressed_by,
+            } if !suppressed_by
+                .iter()
+                .any(|damage_type| taken.contains(damage_type)) =>
+            {
+                Some(healing.clone())
+            }
+            _ => None,
+        })?;
+        if self.current_hp == 0 {
+            return None;
+        }
+        Some(self.heal(healing.roll().max(0) as usize))
+    }
+
+    /// Damage due at the start of the turn from conditions like burning,
+    /// with the condition dealing it
+    pub fn ongoing_damage(&self) -> Vec<(Condition, DicePool, Option<DamageType>)> {
+        self.active_conditions
+            .iter()
+            .flat_map(|condition| {
+                condition
+                    .effects
+                    .iter()
+                    .filter_map(move |effect| match effect {
+                        Effect::OngoingDamage { dice, damage_type } => {
+                            Some((condition.clone(), dice.clone(), *damage_type))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    /// Auras that go off at `at` in the character's turn
+    pub fn auras(&self, at: TurnPoint) -> Vec<Action> {
+        self.triggers
+            .iter()
+            .filter_map(|trigger| match trigger {
+                Trigger::Aura { action, at: when } if *when == at => Some(action.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn with_legendary_actions(mut self, legendary: LegendaryActions) -> Self {
+        self.legendary_actions_left = legendary.per_round;
+        self.legendary_actions = Some(legendary);
+        self
+    }
+
+    /// Legendary actions the character could take right now, if it can act
+    pub fn legendary_options(&self) -> Vec<Action> {
+        match &self.legendary_actions {
+            Some(legendary) if !self.is_incapacitated() && self.current_hp > 0 => legendary
+                .affordable(self.legendary_actions_left)
+                .map(|option| option.action.clone())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Takes `action`'s cost out of the legendary action pool
+    pub fn spend_legendary_action(&mut self, action: &Action) {
+        let cost = self
+            .legendary_actions
+            .as_ref()
+            .and_then(|legendary| legendary.options.iter().find(|o| o.action == *action))
+            .map_or(0, |option| option.cost);
+        self.legendary_actions_left = self.legendary_actions_left.saturating_sub(cost);
+    }
+
+    /// How close it wants to get to its enemies: its longest melee reach, or
+    /// with nothing but ranged actions, its longest normal range. None if it
+    /// has nothing to use on them.
+    pub fn preferred_range(&self) -> Option<Range> {
+        let ranges: Vec<Range> = self
+            .actions
+            .iter()
+            .filter(|action| action.is_hostile())
+            .filter_map(Action::range)
+            .collect();
+        let longest = |melee: bool| {
+            ranges
+                .iter()
+                .filter(|range| range.is_melee() == melee)
+                .max_by_key(|range| range.normal())
+                .copied()
+        };
+        longest(true).or_else(|| longest(false))
+    }
+
+    /// Where it lines up in zones: in front with a melee action, in the
+    /// ranged zone with only ranged ones and in the back with neither
+    pub fn default_zone(&self) -> Zone {
+        match self.preferred_range() {
+            Some(range) if range.is_melee() => Zone::Front,
+            Some(_) => Zone::Ranged,
+            None => Zone::Back,
+        }
+    }
+
+    /// Attacks the character makes when taking `action`
+    pub fn attacks_for(&self, action: &Action) -> usize {
+        match action {
+            Action::Attack { .. } => {
+                1 + self
+                    .features
+                    .iter()
+                    .map(|feature| match feature {
+                        Feature::ExtraAttack { attacks } => *attacks,
+                        _ => 0,
+                    })
+                    .max()
+                    .unwrap_or(0)
+            }
+            _ => 1,
+        }
+    }
+
     pub fn end_turn(&mut self) {
-        let surviving_conditions: Vec<Condition> = self
+        self.active_conditions = self
             .active_conditions
             .iter()
-            .filter_map(|con| {
-                if con.duration.remaining() > 1 {
-                    Some(Condition::copy_deprecate_duration(con))
-                } else {
-                    None
-                }
-            })
+            .filter(|con| con.duration.remaining() > 1)
+            .map(Condition::copy_deprecate_duration)
             .collect();
-        self.active_conditions = surviving_conditions;
+
+        let mut i = 0;
+        while i < self.active_conditions.len() {
+            let save_ends = self.active_conditions[i].save_ends;
+            match save_ends {
+                Some((ability, dc)) if self.saving_throw(ability, dc) => {
+                    self.active_conditions.remove(i);
+                }
+                _ => i += 1,
+            }
+        }
     }
 
     pub fn has_disadvantage_on(&self, action: &Action) -> bool {
@@ -204,6 +1153,28 @@ impl Character {
     }
 }
 
+/// A saving throw against `dc` for `damage`, or half of it on a success
+fn forced_save(
+    target: &mut Character,
+    ability: Ability,
+    dc: usize,
+    damage: &DicePool,
+    damage_type: Option<DamageType>,
+    half_on_success: bool,
+    cover: Cover,
+) -> ActionResult {
+    let saved = target.saving_throw_with_cover(ability, dc, cover);
+    let damage = match (saved, half_on_success) {
+        (false, _) => damage.roll(),
+        (true, true) => damage.roll() / 2,
+        (true, false) => 0,
+    };
+    ActionResult::Save {
+        saved,
+        damage: target.take_damage_of(damage, damage_type),
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct InitiativeEntry {
     pub team: Team,
@@ -211,17 +1182,71 @@ pub struct InitiativeEntry {
     pub initiative: isize,
 }
 
-#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ResourceType {
     SpellSlot(usize),
     Feature(String),
     Points(String),
+    /// One per level; spent on short rests to heal and half come back on a
+    /// long rest
+    HitDice,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Ability {
+    #[serde(rename = "str")]
+    Strength,
+    #[serde(rename = "dex")]
+    Dexterity,
+    #[serde(rename = "con")]
+    Constitution,
+    #[serde(rename = "int")]
+    Intelligence,
+    #[serde(rename = "wis")]
+    Wisdom,
+    #[serde(rename = "cha")]
+    Charisma,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rest {
+    Short,
+    #[default]
+    Long,
+    /// The start of a new day, for "per day" and "at dawn" features
+    Dawn,
+}
+
+/// When a resource comes back, and how much of it. A long rest also counts
+/// as a short rest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recharge {
+    pub on: Rest,
+    /// Amount regained; everything when `None`
+    pub dice: Option<DicePool>,
+    /// "Recharge 5-6": also comes back in full at the start of the
+    /// creature's turn on a d6 roll of at least this
+    pub roll: Option<usize>,
+}
+
+impl Recharge {
+    fn applies_to(&self, rest: Rest) -> bool {
+        matches!(
+            (self.on, rest),
+            (Rest::Short, Rest::Short | Rest::Long)
+                | (Rest::Long, Rest::Long)
+                | (Rest::Dawn, Rest::Dawn)
+        )
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 struct Resources {
     resources: HashMap<ResourceType, usize>,
     max_resources: HashMap<ResourceType, usize>,
+    recharges: HashMap<ResourceType, Recharge>,
 }
 
 impl Resources {
@@ -229,9 +1254,18 @@ impl Resources {
         Resources {
             resources: HashMap::new(),
             max_resources: HashMap::new(),
+            recharges: HashMap::new(),
         }
     }
 
+    fn set_recharge(&mut self, resource_type: ResourceType, recharge: Recharge) {
+        self.recharges.insert(resource_type, recharge);
+    }
+
+    fn max(&self, resource_type: &ResourceType) -> usize {
+        self.max_resources.get(resource_type).copied().unwrap_or(0)
+    }
+
     fn add_max(&mut self, resource_type: ResourceType, amount: usize) {
         self.max_resources
             .entry(resource_type.clone())
@@ -246,6 +1280,14 @@ impl Resources {
         }
     }
 
+    fn total(&self) -> usize {
+        self.resources.values().sum()
+    }
+
+    fn max_total(&self) -> usize {
+        self.max_resources.values().sum()
+    }
+
     fn spend(&mut self, resource_type: ResourceType, amount: usize) -> Result<(), String> {
         let r = self.resources.get_mut(&resource_type);
         match r {
@@ -264,21 +1306,64 @@ impl Resources {
         }
     }
 
-    fn _recover(&mut self, resource_type: ResourceType, amount: usize) {
+    fn recover(&mut self, resource_type: ResourceType, amount: usize) {
         let current = self.resources.get_mut(&resource_type);
         let max = self.max_resources.get(&resource_type).unwrap();
 
-        match current {
-            Some(r) => {
-                if *r + amount > *max {
-                    *r = *max;
-                } else {
-                    *r += amount;
-                }
+        if let Some(r) = current {
+            if *r + amount > *max {
+                *r = *max;
+            } else {
+                *r += amount;
+            }
+        }
+    }
+
+    /// Recovers everything that recharges on `rest`. Resources without a
+    /// recharge rule come back on a long rest.
+    fn rest(&mut self, rest: Rest) {
+        let resource_types: Vec<ResourceType> = self.max_resources.keys().cloned().collect();
+        for resource_type in resource_types {
+            let max = self.max(&resource_type);
+            let amount = match (&resource_type, self.recharges.get(&resource_type)) {
+                (_, Some(recharge)) if recharge.applies_to(rest) => match &recharge.dice {
+                    Some(dice) => dice.roll().max(0) as usize,
+                    None => max,
+                },
+                (_, Some(_)) => 0,
+                (ResourceType::HitDice, None) if rest == Rest::Long => (max / 2).max(1),
+                (_, None) if rest == Rest::Long => max,
+                _ => 0,
+            };
+            self.recover(resource_type, amount);
+        }
+    }
+
+    /// Rolls for every spent resource with a recharge roll
+    fn start_of_turn(&mut self) {
+        let mut spent: Vec<(ResourceType, usize)> = self
+            .recharges
+            .iter()
+            .filter_map(|(resource_type, recharge)| Some((resource_type.clone(), recharge.roll?)))
+            .filter(|(resource_type, _)| self.get(resource_type) < self.max(resource_type))
+            .collect();
+        // Same order every time, so seeded runs roll the same dice
+        spent.sort();
+        for (resource_type, min) in spent {
+            if DicePool::new().add_dice(1, 6).roll() >= min as isize {
+                let max = self.max(&resource_type);
+                self.recover(resource_type, max);
             }
-            None => {}
         }
     }
+
+    fn short_rest(&mut self) {
+        self.rest(Rest::Short);
+    }
+
+    fn long_rest(&mut self) {
+        self.rest(Rest::Long);
+    }
 }
 
 #[cfg(test)]
@@ -331,37 +1416,279 @@ mod resource_tests {
         assert_eq!(resources.get(&ResourceType::Points("Ki".into())), 3);
 
         // Recovery up to max
-        resources._recover(ResourceType::Points("Ki".into()), 1);
+        resources.recover(ResourceType::Points("Ki".into()), 1);
         assert_eq!(resources.get(&ResourceType::Points("Ki".into())), 4);
 
         // Can't recover beyond max
-        resources._recover(ResourceType::Points("Ki".into()), 2);
+        resources.recover(ResourceType::Points("Ki".into()), 2);
         assert_eq!(resources.get(&ResourceType::Points("Ki".into())), 5);
     }
 
-    //   #[test]
-    //    fn test_rests() {
-    //        let mut resources = Resources::new();
-    //        resources.add_max(ResourceType::SpellSlot(1), 4);
-    //        resources.add_max(ResourceType::Feature("Second Wind".into()), 1);
-    //        resources.add_max(ResourceType::Points("Ki".into()), 5);
-
-    //        // Use some resources
-    //        resources.spend(ResourceType::SpellSlot(1), 2).unwrap();
-    //        resources.spend(ResourceType::Feature("Second Wind".into()), 1).unwrap();
-    //        resources.spend(ResourceType::Points("Ki".into()), 3).unwrap();
-
-    //        // Short rest recovers some
-    //        resources.short_rest();
-    //        assert_eq!(resources.get(ResourceType::SpellSlot(1)), 2); // Unchanged
-    //        assert_eq!(resources.get(ResourceType::Feature("Second Wind".into())), 1); // Recovered
-    //        assert_eq!(resources.get(ResourceType::Points("Ki".into())), 5); // Recovered
-
-    //        // Long rest recovers all
-    //        resources.spend(ResourceType::SpellSlot(1), 2).unwrap();
-    //        resources.long_rest();
-    //        assert_eq!(resources.get(ResourceType::SpellSlot(1)), 4);
-    //        assert_eq!(resources.get(ResourceType::Feature("Second Wind".into())), 1);
-    //        assert_eq!(resources.get(ResourceType::Points("Ki".into())), 5);
-    //    }
+    #[test]
+    fn test_rests() {
+        let mut resources = Resources::new();
+        resources.add_max(ResourceType::SpellSlot(1), 4);
+        resources.add_max(ResourceType::Feature("Second Wind".into()), 1);
+        resources.add_max(ResourceType::Points("Ki".into()), 5);
+        let short_rest = Recharge {
+            on: Rest::Short,
+            dice: None,
+            roll: None,
+        };
+        resources.set_recharge(
+            ResourceType::Feature("Second Wind".into()),
+            short_rest.clone(),
+        );
+        resources.set_recharge(ResourceType::Points("Ki".into()), short_rest);
+
+        // Use some resources
+        resources.spend(ResourceType::SpellSlot(1), 2).unwrap();
+        resources
+            .spend(ResourceType::Feature("Second Wind".into()), 1)
+            .unwrap();
+        resources
+            .spend(ResourceType::Points("Ki".into()), 3)
+            .unwrap();
+
+        // Short rest recovers some
+        resources.short_rest();
+        assert_eq!(resources.get(&ResourceType::SpellSlot(1)), 2); // Unchanged
+        assert_eq!(
+            resources.get(&ResourceType::Feature("Second Wind".into())),
+            1
+        ); // Recovered
+        assert_eq!(resources.get(&ResourceType::Points("Ki".into())), 5); // Recovered
+
+        // Long rest recovers all
+        resources.spend(ResourceType::SpellSlot(1), 2).unwrap();
+        resources.long_rest();
+        assert_eq!(resources.get(&ResourceType::SpellSlot(1)), 4);
+        assert_eq!(
+            resources.get(&ResourceType::Feature("Second Wind".into())),
+            1
+        );
+        assert_eq!(resources.get(&ResourceType::Points("Ki".into())), 5);
+    }
+
+    #[test]
+    fn test_dice_and_dawn_recharges() {
+        let mut resources = Resources::new();
+        resources.add_max(ResourceType::Points("Wand Charges".into()), 7);
+        resources.set_recharge(
+            ResourceType::Points("Wand Charges".into()),
+            Recharge {
+                on: Rest::Dawn,
+                dice: Some(DicePool::from_str("1d6+1").unwrap()),
+                roll: None,
+            },
+        );
+        resources
+            .spend(ResourceType::Points("Wand Charges".into()), 7)
+            .unwrap();
+
+        // Dawn features don't come back on a rest
+        resources.long_rest();
+        assert_eq!(
+            resources.get(&ResourceType::Points("Wand Charges".into())),
+            0
+        );
+
+        resources.rest(Rest::Dawn);
+        let charges = resources.get(&ResourceType::Points("Wand Charges".into()));
+        assert!((2..=7).contains(&charges));
+    }
+
+    #[test]
+    fn test_recharge_on_a_roll() {
+        let breath = ResourceType::Feature("Fire Breath".into());
+        let tail = ResourceType::Feature("Tail Sweep".into());
+        let mut dragon = Character::new("Dragon", 100, 18, Team::Monsters, 0);
+        dragon.add_resource(breath.clone(), 1);
+        dragon.add_resource(tail.clone(), 1);
+        // Any roll brings the breath back; no roll brings the tail back
+        for (resource, roll) in [(&breath, 1), (&tail, 7)] {
+            dragon.set_recharge(
+                resource.clone(),
+                Recharge {
+                    on: Rest::Long,
+                    dice: None,
+                    roll: Some(roll),
+                },
+            );
+        }
+
+        let fire_breath = Action::Save {
+            name: "Fire Breath".into(),
+            ability: Ability::Dexterity,
+            dc: 30,
+            damage: "20".into(),
+            damage_type: None,
+            range: Range::default(),
+            half_on_success: true,
+            targets: 3,
+            required_resources: vec![(breath.clone(), 1)],
+        };
+        assert_eq!(fire_breath.target_count(), 3);
+        let mut knight = Character::new("Knight", 50, 18, Team::Heroes, 0);
+        match dragon.take_action(&mut knight, &fire_breath) {
+            ActionResult::Save { saved, damage } => {
+                assert!(!saved);
+                assert_eq!(damage, 20);
+            }
+            _ => panic!("Fire Breath calls for a save"),
+        }
+        assert!(!dragon.has_resource(&breath, 1));
+        assert!(!fire_breath.is_valid(&dragon, &vec![], &vec![knight]));
+
+        dragon.spend_resource(tail.clone(), 1).unwrap();
+        dragon.start_turn(false);
+        assert!(dragon.has_resource(&breath, 1));
+        assert!(!dragon.has_resource(&tail, 1));
+    }
+
+    #[test]
+    fn test_choosing_a_slot_level() {
+        let cure_wounds = Action::CastSpell {
+            spell: Spell::named("Cure Wounds").unwrap(),
+            slot_level: None,
+        };
+        let mut cleric = Character::new("Cleric", 20, 16, Team::Heroes, 0)
+            .with_level(3)
+            .with_spellcasting(Spellcasting {
+                modifier: 3,
+                ..Spellcasting::default()
+            })
+            .with_actions(vec![cure_wounds.clone()]);
+        cleric.current_hp = 1;
+        let allies = vec![cleric.clone()];
+
+        // Four 1st-level slots and two 2nd-level ones, one choice per level
+        let slot_levels: Vec<_> = cleric
+            .valid_actions(&allies, &vec![])
+            .into_iter()
+            .map(|action| match action {
+                Action::CastSpell { slot_level, .. } => slot_level,
+                _ => panic!("the cleric only knows Cure Wounds"),
+            })
+            .collect();
+        assert_eq!(slot_levels, vec![Some(1), Some(2)]);
+
+        let mut target = cleric.clone();
+        let upcast = Action::CastSpell {
+            spell: Spell::named("Cure Wounds").unwrap(),
+            slot_level: Some(2),
+        };
+        match cleric.take_action(&mut target, &upcast) {
+            // 2d8+3
+            ActionResult::Heal { amount } => assert!((5..=19).contains(&amount)),
+            _ => panic!("Cure Wounds should heal"),
+        }
+        assert_eq!(cleric.resources.get(&ResourceType::SpellSlot(2)), 1);
+        assert_eq!(cleric.resources.get(&ResourceType::SpellSlot(1)), 4);
+
+        // Without a level the lowest slot left gets used
+        cleric.take_action(&mut target, &cure_wounds);
+        assert_eq!(cleric.resources.get(&ResourceType::SpellSlot(1)), 3);
+
+        // Slots come back on a long rest
+        cleric.long_rest();
+        assert_eq!(cleric.resources.get(&ResourceType::SpellSlot(2)), 2);
+    }
+
+    #[test]
+    fn test_saving_throw_spell() {
+        let shatter = Action::CastSpell {
+            spell: Spell::named("Shatter").unwrap(),
+            slot_level: Some(2),
+        };
+        let mut wizard = Character::new("Wizard", 20, 12, Team::Heroes, 0)
+            .with_level(3)
+            .with_spellcasting(Spellcasting {
+                save_dc: 30,
+                ..Spellcasting::default()
+            });
+        let mut ogre = Character::new("Ogre", 59, 11, Team::Monsters, 0);
+        match wizard.take_action(&mut ogre, &shatter) {
+            ActionResult::Save { saved, damage } => {
+                assert!(!saved);
+                assert!((3..=24).contains(&damage));
+                assert_eq!(ogre.current_hp, 59 - damage);
+            }
+            _ => panic!("Shatter calls for a save"),
+        }
+
+        // A sure save on a half-damage spell still hurts
+        let mut golem =
+            Character::new("Golem", 59, 11, Team::Monsters, 0).with_save(Ability::Constitution, 40);
+        match wizard.take_action(&mut golem, &shatter) {
+            ActionResult::Save { saved, damage } => {
+                assert!(saved);
+                assert!((1..=12).contains(&damage));
+            }
+            _ => panic!("Shatter calls for a save"),
+        }
+    }
+
+    #[test]
+    fn test_cover() {
+        let mut dragon = Character::new("Dragon", 200, 18, Team::Monsters, 0);
+        let mut rogue =
+            Character::new("Rogue", 1000, 15, Team::Heroes, 0).with_save(Ability::Dexterity, 15);
+        let fire_breath = Action::Save {
+            name: "Fire Breath".into(),
+            ability: Ability::Dexterity,
+            dc: 21,
+            damage: "1".into(),
+            damage_type: None,
+            half_on_success: false,
+            targets: 1,
+            required_resources: vec![],
+            range: Range::within(30),
+        };
+        // +5 to DEX saves turns a likely success into a sure one
+        for _ in 0..50 {
+            match dragon.take_action_with_cover(&mut rogue, &fire_breath, Cover::ThreeQuarters) {
+                ActionResult::Save { saved, .. } => assert!(saved),
+                _ => panic!("Fire Breath calls for a save"),
+            }
+        }
+        let failures = (0..200)
+            .filter(|_| {
+                matches!(
+                    dragon.take_action(&mut rogue, &fire_breath),
+                    ActionResult::Save { saved: false, .. }
+                )
+            })
+            .count();
+        assert!(failures > 0);
+        assert_eq!(Cover::ThreeQuarters.save_bonus(Ability::Wisdom), 0);
+        assert_eq!(Cover::Half.ac_bonus(), 2);
+    }
+
+    #[test]
+    fn test_hit_dice() {
+        let mut fighter = Character::new("Fighter", 30, 16, Team::Heroes, 0)
+            .with_level(4)
+            .with_hit_die(DicePool::from_str("1d10+2").unwrap());
+        assert!(fighter.has_resource(&ResourceType::HitDice, 4));
+
+        // Not worth a hit die
+        fighter.current_hp = 27;
+        fighter.short_rest();
+        assert_eq!(fighter.current_hp, 27);
+        assert!(fighter.has_resource(&ResourceType::HitDice, 4));
+
+        fighter.current_hp = 5;
+        fighter.short_rest();
+        assert!(fighter.current_hp > 5);
+        assert!(!fighter.has_resource(&ResourceType::HitDice, 4));
+
+        // A long rest restores HP and half the hit dice
+        fighter.current_hp = 0;
+        while fighter.spend_resource(ResourceType::HitDice, 1).is_ok() {}
+        fighter.long_rest();
+        assert_eq!(fighter.current_hp, 30);
+        assert!(fighter.has_resource(&ResourceType::HitDice, 2));
+        assert!(!fighter.has_resource(&ResourceType::HitDice, 3));
+    }
 }
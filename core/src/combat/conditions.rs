@@ -1,7 +1,33 @@
-#[derive(Copy, Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::DicePool;
+
+use super::{Ability, DamageType};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ConditionType {
     Poisoned,
     Stunned,
+    Paralyzed,
+    Raging,
+    /// From Reckless Attack, until the creature's next turn
+    Reckless,
+    /// Hidden with Cunning Action until the creature's next attack
+    Hidden,
+    /// Fights for whoever charmed it, as with Dominate Person
+    Charmed,
+    /// A spell's lingering effect, e.g. being blessed, named after the spell
+    Spell(String),
+}
+
+impl ConditionType {
+    pub fn name(&self) -> String {
+        match self {
+            ConditionType::Spell(name) => name.clone(),
+            other => format!("{:?}", other),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -9,32 +35,92 @@ pub struct Condition {
     pub condition: ConditionType,
     pub duration: Duration,
     pub effects: Vec<Effect>,
+    /// The concentration keeping the condition going, if any
+    pub concentration: Option<Concentration>,
+    /// Save the affected creature repeats at the end of each of its turns,
+    /// ending the condition on a success
+    pub save_ends: Option<(Ability, usize)>,
+    /// Name of the creature that imposed the condition
+    pub source: Option<String>,
+}
+
+/// A caster concentrating on a spell. Conditions linked to it end as soon as
+/// the caster stops concentrating.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Concentration {
+    pub caster: String,
+    pub spell: String,
 }
 
 impl Condition {
-    pub fn poisoned(duration: Duration) -> Self {
+    pub fn new(condition: ConditionType, duration: Duration) -> Self {
+        let effects = match condition {
+            ConditionType::Poisoned => vec![Effect::DisadvantageOnAttacks],
+            ConditionType::Stunned | ConditionType::Paralyzed => {
+                vec![Effect::CantTakeActions, Effect::AttackersHaveAdvantage]
+            }
+            ConditionType::Raging => vec![Effect::Resistance {
+                damage_types: vec![
+                    DamageType::Bludgeoning,
+                    DamageType::Piercing,
+                    DamageType::Slashing,
+                ],
+            }],
+            ConditionType::Reckless => {
+                vec![Effect::AdvantageOnAttacks, Effect::AttackersHaveAdvantage]
+            }
+            ConditionType::Hidden => vec![Effect::AdvantageOnAttacks],
+            ConditionType::Charmed => vec![Effect::SwitchesSides],
+            ConditionType::Spell(_) => vec![],
+        };
         Condition {
-            condition: ConditionType::Poisoned,
+            condition,
             duration,
-            effects: vec![Effect::DisadvantageOnAttacks],
+            effects,
+            concentration: None,
+            save_ends: None,
+            source: None,
         }
     }
 
+    pub fn poisoned(duration: Duration) -> Self {
+        Condition::new(ConditionType::Poisoned, duration)
+    }
+
     pub fn stunned(duration: Duration) -> Self {
-        Condition {
-            condition: ConditionType::Stunned,
-            duration,
-            effects: vec![Effect::CantTakeActions],
-        }
+        Condition::new(ConditionType::Stunned, duration)
+    }
+
+    pub fn paralyzed(duration: Duration) -> Self {
+        Condition::new(ConditionType::Paralyzed, duration)
+    }
+
+    pub fn with_effects(mut self, mut effects: Vec<Effect>) -> Self {
+        self.effects.append(&mut effects);
+        self
+    }
+
+    pub fn with_source<T: Into<String>>(mut self, source: T) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    pub fn with_concentration(mut self, concentration: Concentration) -> Self {
+        self.concentration = Some(concentration);
+        self
+    }
+
+    pub fn with_save_ends(mut self, ability: Ability, dc: usize) -> Self {
+        self.save_ends = Some((ability, dc));
+        self
     }
 
     pub fn copy_deprecate_duration(condition: &Condition) -> Self {
-        let mut duration = condition.duration.clone();
+        let mut duration = condition.duration;
         duration.deprecate();
         Condition {
-            condition: condition.condition.clone(),
             duration,
-            effects: condition.effects.clone(),
+            ..condition.clone()
         }
     }
 
@@ -78,22 +164,72 @@ impl Duration {
     }
 }
 
+#[allow(clippy::from_over_into)]
 impl Into<Duration> for usize {
     fn into(self) -> Duration {
         Duration::Rounds(self)
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
 pub enum Effect {
+    AdvantageOnAttacks,
     DisadvantageOnAttacks,
+    AttackersHaveAdvantage,
     CantTakeActions,
+    /// Fights for the faction of the condition's source
+    SwitchesSides,
+    /// Takes half damage of these types
+    Resistance {
+        damage_types: Vec<DamageType>,
+    },
+    /// Rolled and added to the creature's attack rolls; negative dice for
+    /// penalties like Bane's
+    AttackRolls {
+        dice: DicePool,
+    },
+    SavingThrows {
+        dice: DicePool,
+    },
+    ArmorClass {
+        bonus: isize,
+    },
+    /// Extra damage on each of the creature's hits
+    DamageOnHit {
+        dice: DicePool,
+    },
+    /// Extra damage on each of the creature's melee weapon hits, e.g. from
+    /// Rage
+    MeleeWeaponDamage {
+        dice: DicePool,
+    },
+    /// Extra damage the condition's source deals to this creature on a hit,
+    /// e.g. from Hunter's Mark
+    DamageFromSource {
+        dice: DicePool,
+    },
+    /// Change to walking speed in feet
+    Speed {
+        change: isize,
+    },
+    /// Damage taken at the start of each of the creature's turns, e.g. from
+    /// burning or bleeding
+    OngoingDamage {
+        dice: DicePool,
+        #[serde(default)]
+        damage_type: Option<DamageType>,
+    },
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Action, Character, Team};
+    use crate::{
+        battlefield::Range,
+        combat::{ResourceType, Spell},
+        Action, Character, Team,
+    };
 
     #[test]
     fn test_basic_conditions() {
@@ -117,6 +253,8 @@ mod test {
             name: "Attack".into(),
             hit_bonus: 1,
             damage: "1d4".into(),
+            damage_type: None,
+            range: Range::default(),
         };
         fighter.add_action(attack.clone());
         let monster = fighter.clone();
@@ -134,6 +272,82 @@ mod test {
         assert!(!fighter.valid_actions(&vec![], &monsters).is_empty());
     }
 
+    #[test]
+    fn test_roll_modifiers() {
+        let mut cleric = Character::new("Cleric", 10, 16, Team::Heroes, 0);
+        let shield_of_faith =
+            Condition::new(ConditionType::Spell("Shield of Faith".into()), 10.into())
+                .with_effects(vec![Effect::ArmorClass { bonus: 2 }]);
+        cleric.add_condition(shield_of_faith);
+        assert_eq!(cleric.armor_class(), 18);
+
+        cleric.add_condition(
+            Condition::new(ConditionType::Spell("Slowed".into()), 10.into())
+                .with_effects(vec![Effect::Speed { change: -40 }]),
+        );
+        assert_eq!(cleric.current_speed(), 0);
+
+        // Bane's -1d4 makes a DC 20 save impossible without a bonus
+        cleric.add_condition(
+            Condition::new(ConditionType::Spell("Bane".into()), 10.into()).with_effects(vec![
+                Effect::SavingThrows {
+                    dice: "-1d4".into(),
+                },
+            ]),
+        );
+        assert!((0..100).all(|_| !cleric.saving_throw(Ability::Wisdom, 20)));
+
+        cleric.add_condition(
+            Condition::new(ConditionType::Spell("Bless".into()), 10.into())
+                .with_effects(vec![Effect::SavingThrows { dice: "24".into() }]),
+        );
+        assert!((0..100).all(|_| cleric.saving_throw(Ability::Wisdom, 21)));
+    }
+
+    #[test]
+    fn test_extra_damage_on_hit() {
+        let mut ranger =
+            Character::new("Ranger", 10, 15, Team::Heroes, 0).with_spellcasting(Default::default());
+        let hunters_mark = Action::CastSpell {
+            spell: Spell::named("Hunter's Mark").unwrap(),
+            slot_level: Some(1),
+        };
+        ranger.add_resource(ResourceType::SpellSlot(1), 1);
+        let mut marked = Character::new("Marked", 1000, 10, Team::Monsters, 0);
+        let mut unmarked = marked.clone();
+        ranger.take_action(&mut marked, &hunters_mark);
+
+        let longbow = Action::Attack {
+            name: "Longbow".into(),
+            hit_bonus: 30,
+            damage: "1".into(),
+            damage_type: None,
+            range: Range::default(),
+        };
+        for _ in 0..20 {
+            let before = (marked.current_hp, unmarked.current_hp);
+            ranger.take_action(&mut marked, &longbow);
+            ranger.take_action(&mut unmarked, &longbow);
+            let dealt = (before.0 - marked.current_hp, before.1 - unmarked.current_hp);
+            // Natural 1s miss; otherwise only the marked target takes the d6
+            assert!(dealt.0 == 0 || (2..=13).contains(&dealt.0));
+            assert!(dealt.1 <= 1);
+        }
+    }
+
+    #[test]
+    fn test_save_ends() {
+        let mut fighter = Character::new("Fighter", 10, 15, Team::Heroes, 1);
+        fighter.add_condition(Condition::paralyzed(10.into()).with_save_ends(Ability::Wisdom, 30));
+        fighter.end_turn();
+        assert!(fighter.has_condition(ConditionType::Paralyzed));
+
+        // A save that can't fail shakes it off at the end of the next turn
+        fighter.saves.insert(Ability::Wisdom, 40);
+        fighter.end_turn();
+        assert!(!fighter.has_condition(ConditionType::Paralyzed));
+    }
+
     // #[test]
     // fn test_condition_durations() {
     //     let mut fighter = Character::new("Fighter", 10, 15, Team::Heroes, 1);
@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+/// Only matters to the traits that care about it, like a troll's
+/// regeneration; untyped damage is `None` wherever a type is optional
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DamageType {
+    Acid,
+    Bludgeoning,
+    Cold,
+    Fire,
+    Force,
+    Lightning,
+    Necrotic,
+    Piercing,
+    Poison,
+    Psychic,
+    Radiant,
+    Slashing,
+    Thunder,
+}
@@ -0,0 +1,167 @@
+use std::{cell::RefCell, rc::Rc};
+
+use serde::{Deserialize, Serialize};
+
+use crate::battlefield::Cover;
+
+use super::{Ability, Action, ConditionType, Departure};
+
+/// Something that happened during a fight, as it happened. Creatures are
+/// referred to by name.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum Event {
+    TurnStarted {
+        round: usize,
+        creature: String,
+        hp: usize,
+    },
+    /// A d20 roll, with the AC or DC it had to meet if any
+    Rolled {
+        creature: String,
+        roll: Roll,
+        total: isize,
+        against: Option<isize>,
+    },
+    /// An action or trigger about to be carried out against `targets`
+    ActionTaken {
+        actor: String,
+        cause: Cause,
+        targets: Vec<String>,
+    },
+    AttackHit {
+        attacker: String,
+        target: String,
+        critical: bool,
+        cover: Cover,
+    },
+    AttackMissed {
+        attacker: String,
+        target: String,
+        cover: Cover,
+    },
+    /// Damage taken, from `source` if it came from a creature, leaving the
+    /// target on `hp`
+    DamageApplied {
+        source: Option<String>,
+        target: String,
+        amount: usize,
+        hp: usize,
+    },
+    Healed {
+        source: Option<String>,
+        target: String,
+        amount: usize,
+        hp: usize,
+    },
+    ConditionAdded {
+        creature: String,
+        condition: ConditionType,
+    },
+    ConditionRemoved {
+        creature: String,
+        condition: ConditionType,
+    },
+    ResourceSpent {
+        creature: String,
+        amount: usize,
+    },
+    Downed {
+        creature: String,
+    },
+    /// Out of the fight without going down
+    Departed {
+        creature: String,
+        departure: Departure,
+    },
+    RoundEnded {
+        round: usize,
+    },
+}
+
+/// What an event is about: an action, or something that happened on its
+/// own at the start or end of a turn, like regeneration
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(clippy::large_enum_variant)]
+pub enum Cause {
+    Action(Action),
+    Trigger(String),
+}
+
+/// What a d20 was rolled for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Roll {
+    Initiative,
+    Attack,
+    Save { ability: Ability },
+    Morale { ability: Ability },
+}
+
+/// Anything that wants to hear about a fight as it happens, like a log, a
+/// stats collector or a UI
+pub trait Observer {
+    fn observe(&mut self, event: &Event);
+}
+
+impl<F: FnMut(&Event)> Observer for F {
+    fn observe(&mut self, event: &Event) {
+        self(event)
+    }
+}
+
+/// Keeps every event. Clones share the same events, so one can be handed
+/// to a fight and the other read once it's over.
+#[derive(Clone, Debug, Default)]
+pub struct EventLog {
+    events: Rc<RefCell<Vec<Event>>>,
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        EventLog::default()
+    }
+
+    pub fn events(&self) -> Vec<Event> {
+        self.events.borrow().clone()
+    }
+}
+
+impl Observer for EventLog {
+    fn observe(&mut self, event: &Event) {
+        self.events.borrow_mut().push(event.clone());
+    }
+}
+
+thread_local! {
+    /// Rolls made since they were last collected, while anyone's listening
+    static ROLLS: RefCell<Option<Vec<Event>>> = const { RefCell::new(None) };
+}
+
+/// Starts or stops keeping rolls on this thread. Creatures roll without
+/// knowing who's listening, so their rolls wait here to be collected.
+pub(crate) fn keep_rolls(keep: bool) {
+    ROLLS.with(|rolls| *rolls.borrow_mut() = keep.then(Vec::new));
+}
+
+/// Keeps a roll if rolls are being kept; `event` is only built if so
+pub(crate) fn record_roll(event: impl FnOnce() -> Event) {
+    ROLLS.with(|rolls| {
+        if let Some(rolls) = rolls.borrow_mut().as_mut() {
+            rolls.push(event());
+        }
+    });
+}
+
+/// The rolls kept since last time
+pub(crate) fn take_rolls() -> Vec<Event> {
+    ROLLS.with(|rolls| {
+        rolls
+            .borrow_mut()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    })
+}
@@ -0,0 +1,207 @@
+use serde::{Deserialize, Serialize};
+
+use crate::DicePool;
+
+/// Class features that change how a creature's turns and attacks play out.
+/// Features with limited uses spend a `ResourceType::Feature` of the same
+/// name, e.g. `{ feature = "Rage" }`, which the creature needs as well.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Feature {
+    /// Extra dice once per turn on a weapon hit with advantage or with an
+    /// ally next to the target
+    SneakAttack { dice: DicePool },
+    /// Bonus action for resistance and `damage` extra on every hit, for up
+    /// to 10 rounds
+    Rage { damage: isize },
+    /// One extra action, taken as soon as it's available
+    ActionSurge,
+    /// Spends a spell slot on a weapon hit: the highest on a critical, the
+    /// lowest otherwise
+    DivineSmite,
+    /// Bonus action to hide, for advantage on the next attack
+    CunningAction,
+    /// Advantage on attacks, and attacks against the creature have
+    /// advantage until its next turn. Always used.
+    RecklessAttack,
+    /// Attacks beyond the first when taking an attack action
+    ExtraAttack { attacks: usize },
+    /// Spends a use to succeed on a failed saving throw
+    LegendaryResistance,
+}
+
+impl Feature {
+    pub const RAGE: &'static str = "Rage";
+    pub const ACTION_SURGE: &'static str = "Action Surge";
+    pub const LEGENDARY_RESISTANCE: &'static str = "Legendary Resistance";
+
+    /// 2d8 for a 1st-level slot and 1d8 more per level above, up to 5d8
+    pub fn smite_dice(slot_level: usize) -> DicePool {
+        DicePool::new().add_dice((slot_level + 1).min(5), 8)
+    }
+}
+
+#[cfg(test)]
+mod feature_tests {
+    use super::*;
+    use crate::{
+        battlefield::Range,
+        combat::{
+            Ability, CasterType, ConditionType, DamageType, ResourceType, Spell, SpellEffect,
+            Spellcasting,
+        },
+        Action, ActionResult, AdvantageType, Character, HitResult, Team,
+    };
+
+    fn sure_hit(damage: &str) -> Action {
+        sure_hit_at(damage, Range::default())
+    }
+
+    fn sure_hit_at(damage: &str, range: Range) -> Action {
+        Action::Attack {
+            name: "Sure Hit".into(),
+            hit_bonus: 30,
+            damage: damage.into(),
+            damage_type: None,
+            range,
+        }
+    }
+
+    /// Attacks until one lands, as a natural 1 still misses
+    fn hit(attacker: &mut Character, target: &mut Character, action: &Action) -> usize {
+        loop {
+            if let ActionResult::Attack { hit, damage } = attacker.take_action(target, action) {
+                if !matches!(hit, HitResult::Miss) {
+                    return damage;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sneak_attack_once_per_turn() {
+        let mut rogue = Character::new("Rogue", 10, 14, Team::Heroes, 3)
+            .with_feature(Feature::SneakAttack { dice: "10".into() });
+        let mut target = Character::new("Target", 1000, 10, Team::Monsters, 0);
+        let rapier = sure_hit("1");
+
+        rogue.start_turn(true);
+        assert_eq!(hit(&mut rogue, &mut target, &rapier), 11);
+        assert_eq!(hit(&mut rogue, &mut target, &rapier), 1);
+
+        // No ally nearby and no advantage
+        rogue.start_turn(false);
+        assert_eq!(hit(&mut rogue, &mut target, &rapier), 1);
+
+        // Hiding with Cunning Action gives the advantage instead
+        rogue.features.push(Feature::CunningAction);
+        rogue.start_turn(false);
+        assert!(rogue.has_condition(ConditionType::Hidden));
+        assert_eq!(rogue.attack_advantage(&target), AdvantageType::Advantage);
+        assert_eq!(hit(&mut rogue, &mut target, &rapier), 11);
+        assert!(!rogue.has_condition(ConditionType::Hidden));
+    }
+
+    #[test]
+    fn test_rage() {
+        let mut barbarian = Character::new("Barbarian", 50, 14, Team::Heroes, 1)
+            .with_feature(Feature::Rage { damage: 2 })
+            .with_feature(Feature::RecklessAttack);
+        barbarian.add_resource(ResourceType::Feature(Feature::RAGE.into()), 2);
+        let mut target = Character::new("Target", 1000, 10, Team::Monsters, 0);
+
+        barbarian.start_turn(false);
+        assert!(barbarian.has_condition(ConditionType::Raging));
+        assert!(barbarian.has_resource(&ResourceType::Feature(Feature::RAGE.into()), 1));
+        assert!(!barbarian.has_resource(&ResourceType::Feature(Feature::RAGE.into()), 2));
+        assert_eq!(barbarian.take_damage_of(10, Some(DamageType::Slashing)), 5);
+        assert_eq!(barbarian.take_damage_of(10, Some(DamageType::Fire)), 10);
+        assert_eq!(barbarian.take_damage(10), 10);
+
+        // The rage bonus isn't doubled on a critical
+        assert_eq!(hit(&mut barbarian, &mut target, &sure_hit("1")), 3);
+
+        // Only melee weapon attacks get the bonus
+        let javelin = sure_hit_at("1", Range::within(30));
+        assert_eq!(hit(&mut barbarian, &mut target, &javelin), 1);
+        barbarian.spellcasting = Some(Spellcasting {
+            attack_bonus: 30,
+            ..Spellcasting::default()
+        });
+        let spell_attack = Action::CastSpell {
+            spell: Spell {
+                effect: SpellEffect::Attack {
+                    damage: "1".into(),
+                    add_modifier: false,
+                },
+                ..Spell::named("Fire Bolt").unwrap()
+            },
+            slot_level: None,
+        };
+        assert_eq!(hit(&mut barbarian, &mut target, &spell_attack), 1);
+
+        // Already raging, so no second use; reckless both ways
+        barbarian.start_turn(false);
+        assert!(barbarian.has_resource(&ResourceType::Feature(Feature::RAGE.into()), 1));
+        assert_eq!(
+            barbarian.attack_advantage(&target),
+            AdvantageType::Advantage
+        );
+        assert_eq!(
+            target.attack_advantage(&barbarian),
+            AdvantageType::Advantage
+        );
+    }
+
+    #[test]
+    fn test_action_surge() {
+        let mut fighter =
+            Character::new("Fighter", 50, 18, Team::Heroes, 1).with_feature(Feature::ActionSurge);
+        fighter.add_resource(ResourceType::Feature(Feature::ACTION_SURGE.into()), 1);
+        assert_eq!(fighter.start_turn(false), 2);
+        assert_eq!(fighter.start_turn(false), 1);
+
+        fighter.features.push(Feature::ExtraAttack { attacks: 1 });
+        assert_eq!(fighter.attacks_for(&sure_hit("1d8")), 2);
+    }
+
+    #[test]
+    fn test_divine_smite() {
+        let mut paladin = Character::new("Paladin", 44, 18, Team::Heroes, 0)
+            .with_level(5)
+            .with_spellcasting(Spellcasting {
+                caster: CasterType::Half,
+                ..Spellcasting::default()
+            })
+            .with_feature(Feature::DivineSmite);
+        let mut target = Character::new("Target", 1000, 10, Team::Monsters, 0);
+
+        // No smiting with a ranged weapon
+        let longbow = sure_hit_at("1", Range::within(150));
+        assert_eq!(hit(&mut paladin, &mut target, &longbow), 1);
+        assert!(paladin.has_resource(&ResourceType::SpellSlot(1), 4));
+
+        let damage = hit(&mut paladin, &mut target, &sure_hit("1"));
+        let first = paladin.has_resource(&ResourceType::SpellSlot(1), 4);
+        let second = paladin.has_resource(&ResourceType::SpellSlot(2), 2);
+        // A 1st-level slot on a hit, the 2nd-level one on a critical
+        assert!(first ^ second);
+        assert!(damage >= 3);
+        assert_eq!(Feature::smite_dice(4).count_dice(8), 5);
+        assert_eq!(Feature::smite_dice(9).count_dice(8), 5);
+    }
+
+    #[test]
+    fn test_legendary_resistance() {
+        let mut dragon = Character::new("Dragon", 200, 18, Team::Monsters, 0)
+            .with_save(Ability::Wisdom, -40)
+            .with_feature(Feature::LegendaryResistance);
+        let resistance = ResourceType::Feature(Feature::LEGENDARY_RESISTANCE.into());
+        dragon.add_resource(resistance.clone(), 2);
+
+        assert!(dragon.saving_throw(Ability::Wisdom, 10));
+        assert!(dragon.saving_throw(Ability::Wisdom, 10));
+        assert!(!dragon.has_resource(&resistance, 1));
+        assert!(!dragon.saving_throw(Ability::Wisdom, 10));
+    }
+}
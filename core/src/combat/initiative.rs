@@ -0,0 +1,30 @@
+use serde::{Deserialize, Serialize};
+
+/// How the turn order is decided. Ties go to the higher DEX score, then to a
+/// coin flip.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Initiative {
+    /// Everyone rolls their own
+    #[default]
+    Individual,
+    /// Identical creatures share one roll and take their turns together
+    Group,
+    /// Each side rolls an unmodified d20 and takes all its turns together
+    Side,
+    /// The highest roll goes first, then whoever just acted hands the next
+    /// turn to an ally still to act, or to an enemy if there are none left.
+    /// The last to act in a round picks who opens the next one.
+    Popcorn,
+}
+
+/// What being surprised costs a creature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Surprise {
+    /// 2014 rules: it loses its first turn, legendary actions included
+    #[default]
+    LoseTurn,
+    /// 2024 rules: it rolls initiative with disadvantage
+    Disadvantage,
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Action;
+
+/// Actions a legendary creature can take at the end of other creatures'
+/// turns, paid for out of a pool that refills at the start of its own turn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LegendaryActions {
+    pub per_round: usize,
+    pub options: Vec<LegendaryAction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LegendaryAction {
+    #[serde(default = "default_cost")]
+    pub cost: usize,
+    pub action: Action,
+}
+
+fn default_cost() -> usize {
+    1
+}
+
+impl LegendaryActions {
+    /// Options that cost no more than `available`
+    pub fn affordable(&self, available: usize) -> impl Iterator<Item = &LegendaryAction> {
+        self.options
+            .iter()
+            .filter(move |option| option.cost <= available)
+    }
+}
@@ -1,72 +1,121 @@
 mod action;
 mod actor;
 mod conditions;
-mod log;
-
-use std::{collections::HashMap, vec};
+mod damage;
+mod event;
+mod feature;
+mod initiative;
+mod legendary;
+mod morale;
+mod objective;
+mod spell;
+mod tally;
+mod trigger;
 
 pub use action::{Action, ActionResult, HitResult};
-pub use actor::{Character, InitiativeEntry, ResourceType};
-pub use conditions::{Condition, Duration, Effect};
-pub use log::ActivityLog;
+pub use actor::{Ability, Character, InitiativeEntry, Recharge, ResourceType, Rest};
+pub use conditions::{Concentration, Condition, ConditionType, Duration, Effect};
+pub use damage::DamageType;
+pub use event::{Cause, Event, EventLog, Observer, Roll};
+pub use feature::Feature;
+pub use initiative::{Initiative, Surprise};
+pub use legendary::{LegendaryAction, LegendaryActions};
+pub use morale::{Departure, Morale};
+pub use objective::Objective;
+pub use spell::{
+    spell_list, CasterType, CastingTime, Components, Spell, SpellEffect, Spellcasting, Upcast,
+};
+pub use tally::CombatantTally;
+pub use trigger::{Trigger, TurnPoint};
+
+pub(crate) use event::{keep_rolls, take_rolls};
 
-use crate::{Combat, Team};
+use crate::{
+    battlefield::Range,
+    encounter::{CreatureSpec, Encounter, ResourceSpec},
+    Combat,
+};
 
 pub fn build_level_one_combat(num_kobolds: usize) -> Combat {
-    let fighter = Character::new("Fighter", 12, 16, Team::Heroes, 1)
-        .with_actions(vec![
-            Action::Attack {
-                name: "Greatsword".into(),
-                hit_bonus: 5,
-                damage: "2d6+3".into(),
-            },
-            Action::SecondWind {
-                healing: "1d10+1".into(),
-                required_resources: vec![(ResourceType::Feature("Second Wind".into()), 1)],
-            },
-        ])
-        .with_resources(HashMap::from([(
-            ResourceType::Feature("Second Wind".into()),
-            1,
-        )]));
-    let cleric = Character::new("Cleric", 10, 16, Team::Heroes, 0).with_actions(vec![
+    level_one_encounter(num_kobolds).build_combat()
+}
+
+pub fn level_one_encounter(num_kobolds: usize) -> Encounter {
+    let mut fighter = CreatureSpec::new("Fighter", 12, 16);
+    fighter.initiative_bonus = 1;
+    fighter.actions = vec![
+        Action::Attack {
+            name: "Greatsword".into(),
+            hit_bonus: 5,
+            damage: "2d6+3".into(),
+            damage_type: None,
+            range: Range::default(),
+        },
+        Action::SecondWind {
+            healing: "1d10+1".into(),
+            required_resources: vec![(ResourceType::Feature("Second Wind".into()), 1)],
+        },
+    ];
+    fighter.resources = vec![ResourceSpec {
+        resource: ResourceType::Feature("Second Wind".into()),
+        max: 1,
+        recharge: Rest::Short,
+        recharge_dice: None,
+        recharge_roll: None,
+    }];
+    fighter.hit_die = Some("1d10+2".into());
+
+    let mut cleric = CreatureSpec::new("Cleric", 10, 16);
+    cleric.hit_die = Some("1d8+2".into());
+    cleric.actions = vec![
         Action::Attack {
             name: "Mace".into(),
             hit_bonus: 4,
             damage: "1d6+2".into(),
+            damage_type: None,
+            range: Range::default(),
         },
         Action::Heal {
             name: "Healing Word".into(),
             healing: "1d8+3".into(),
             required_resources: vec![],
         },
-    ]);
-    let rogue =
-        Character::new("Rogue", 9, 14, Team::Heroes, 3).with_actions(vec![Action::Attack {
-            name: "Rapier".into(),
-            hit_bonus: 5,
-            damage: "1d8+3".into(),
-        }]);
-    let heroes = vec![fighter, cleric, rogue];
+    ];
+
+    let mut rogue = CreatureSpec::new("Rogue", 9, 14);
+    rogue.initiative_bonus = 3;
+    rogue.hit_die = Some("1d8+1".into());
+    rogue.actions = vec![Action::Attack {
+        name: "Rapier".into(),
+        hit_bonus: 5,
+        damage: "1d8+3".into(),
+        damage_type: None,
+        range: Range::default(),
+    }];
 
-    let kobold_dagger = Action::Attack {
-        name: "Dagger".into(),
-        hit_bonus: 4,
-        damage: "1d4+2".into(),
-    };
-    let kobold_sling = Action::Attack {
-        name: "Sling".into(),
-        hit_bonus: 4,
-        damage: "1d4+2".into(),
-    };
-    let kobold_actions = vec![kobold_dagger, kobold_sling];
-    let monsters = (0..num_kobolds)
-        .into_iter()
-        .map(|i| {
-            Character::new(format!("Kobold {}", i + 1), 5, 12, Team::Monsters, 2)
-                .with_actions(kobold_actions.clone())
-        })
-        .collect();
+    let mut kobold = CreatureSpec::new("Kobold", 5, 12);
+    kobold.initiative_bonus = 2;
+    kobold.challenge_rating = 0.125;
+    kobold.count = num_kobolds;
+    kobold.actions = vec![
+        Action::Attack {
+            name: "Dagger".into(),
+            hit_bonus: 4,
+            damage: "1d4+2".into(),
+            damage_type: None,
+            range: Range::default(),
+        },
+        Action::Attack {
+            name: "Sling".into(),
+            hit_bonus: 4,
+            damage: "1d4+2".into(),
+            damage_type: None,
+            range: Range::Ranged {
+                normal: 30,
+                long: 120,
+            },
+        },
+    ];
 
-    Combat::new(heroes, monsters)
+    Encounter::new(vec![fighter, cleric, rogue], vec![kobold])
 }
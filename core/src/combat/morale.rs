@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+use super::Ability;
+
+/// When a creature's nerve breaks. It checks once, at the start of its
+/// first turn after its faction is down to less than `below` of its numbers
+/// or its `leader` is out of the fight, and a failed save sends it running.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Morale {
+    #[serde(default = "default_below")]
+    pub below: f64,
+    #[serde(default)]
+    pub leader: Option<String>,
+    #[serde(default = "default_dc")]
+    pub dc: usize,
+    #[serde(default = "default_ability")]
+    pub ability: Ability,
+    /// Throws down its weapons instead of fleeing
+    #[serde(default)]
+    pub surrender: bool,
+}
+
+fn default_below() -> f64 {
+    0.5
+}
+
+fn default_dc() -> usize {
+    10
+}
+
+fn default_ability() -> Ability {
+    Ability::Wisdom
+}
+
+impl Default for Morale {
+    fn default() -> Self {
+        Morale {
+            below: default_below(),
+            leader: None,
+            dc: default_dc(),
+            ability: default_ability(),
+            surrender: false,
+        }
+    }
+}
+
+/// How a creature left the fight without going down
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Departure {
+    Fled,
+    Surrendered,
+    /// Got away through an exit, as the heroes' objective
+    Escaped,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+
+use crate::battlefield::Square;
+
+/// What the heroes need to do to win, besides seeing off everyone hostile
+/// to them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Objective {
+    /// Hold out for `rounds` rounds with someone still standing
+    Survive { rounds: usize },
+    /// Take `target` down or out of the fight
+    Kill { target: String },
+    /// Get everyone still standing off the grid through one of the `exits`.
+    /// Heroes head for the nearest one instead of closing in.
+    Escape { exits: Vec<Square> },
+}
@@ -0,0 +1,659 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{battlefield::Range, DicePool};
+
+use super::{Ability, ConditionType, DamageType, Effect, Rest};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CastingTime {
+    #[default]
+    Action,
+    /// Takes the caster's action all the same, there's no separate bonus
+    /// action in a turn yet
+    BonusAction,
+    Reaction,
+    Minutes(usize),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Components {
+    #[serde(default)]
+    pub verbal: bool,
+    #[serde(default)]
+    pub somatic: bool,
+    #[serde(default)]
+    pub material: bool,
+}
+
+/// What a spell does to each of its targets. Dice don't include the
+/// caster's spellcasting modifier unless `add_modifier` is set.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SpellEffect {
+    /// Spell attack roll against AC
+    Attack {
+        damage: DicePool,
+        #[serde(default)]
+        add_modifier: bool,
+    },
+    /// Saving throw against the caster's spell save DC
+    Save {
+        ability: Ability,
+        damage: DicePool,
+        #[serde(default)]
+        half_on_success: bool,
+    },
+    Heal {
+        healing: DicePool,
+        #[serde(default)]
+        add_modifier: bool,
+    },
+    /// Imposes `condition` for up to `rounds` rounds. With a `save`, the
+    /// target resists it on a success and repeats the save at the end of
+    /// each of its turns.
+    Condition {
+        condition: ConditionType,
+        #[serde(default)]
+        save: Option<Ability>,
+        rounds: usize,
+    },
+    /// Effects on allies, or only the caster, for up to `rounds` rounds
+    Buff {
+        effects: Vec<Effect>,
+        rounds: usize,
+        #[serde(default)]
+        self_only: bool,
+    },
+    /// Effects on enemies for up to `rounds` rounds, resisted with a
+    /// successful `save`
+    Debuff {
+        effects: Vec<Effect>,
+        #[serde(default)]
+        save: Option<Ability>,
+        rounds: usize,
+    },
+}
+
+/// Extra effect for each slot level above the spell's level
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Upcast {
+    #[serde(default)]
+    pub dice: Option<DicePool>,
+    #[serde(default)]
+    pub targets: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spell {
+    pub name: String,
+    /// 0 for cantrips
+    pub level: usize,
+    #[serde(default)]
+    pub casting_time: CastingTime,
+    #[serde(default)]
+    pub components: Components,
+    #[serde(default = "default_targets")]
+    pub targets: usize,
+    pub effect: SpellEffect,
+    #[serde(default)]
+    pub upcast: Upcast,
+    #[serde(default)]
+    pub concentration: bool,
+    #[serde(default)]
+    pub damage_type: Option<DamageType>,
+    /// Touch when omitted
+    #[serde(default)]
+    pub range: Range,
+}
+
+fn default_targets() -> usize {
+    1
+}
+
+impl Spell {
+    pub fn is_cantrip(&self) -> bool {
+        self.level == 0
+    }
+
+    /// Spells that take longer than an action can't be cast mid-fight
+    pub fn castable_in_combat(&self) -> bool {
+        matches!(
+            self.casting_time,
+            CastingTime::Action | CastingTime::BonusAction
+        )
+    }
+
+    pub fn targets_at(&self, slot_level: usize) -> usize {
+        self.targets + self.upcast.targets * self.levels_above(slot_level)
+    }
+
+    /// Damage or healing dice cast with a `slot_level` slot by a caster of
+    /// `caster_level`. Cantrips gain a die at 5th, 11th and 17th level.
+    /// Empty for spells that don't roll any.
+    pub fn dice_at(&self, slot_level: usize, caster_level: usize) -> DicePool {
+        let dice = match &self.effect {
+            SpellEffect::Attack { damage, .. } | SpellEffect::Save { damage, .. } => damage,
+            SpellEffect::Heal { healing, .. } => healing,
+            SpellEffect::Condition { .. }
+            | SpellEffect::Buff { .. }
+            | SpellEffect::Debuff { .. } => return DicePool::new(),
+        };
+
+        if self.is_cantrip() {
+            let tier = 1 + [5, 11, 17].iter().filter(|l| caster_level >= **l).count();
+            return dice.multiply_dice(tier);
+        }
+        match &self.upcast.dice {
+            Some(extra) => dice.clone().add_pool(extra, self.levels_above(slot_level)),
+            None => dice.clone(),
+        }
+    }
+
+    fn levels_above(&self, slot_level: usize) -> usize {
+        slot_level.saturating_sub(self.level)
+    }
+
+    /// Looks a spell up in the built-in spell list by name
+    pub fn named(name: &str) -> Option<Spell> {
+        spell_list()
+            .into_iter()
+            .find(|spell| spell.name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CasterType {
+    /// Bard, cleric, druid, sorcerer, wizard
+    #[default]
+    Full,
+    /// Paladin, ranger
+    Half,
+    /// Eldritch knight, arcane trickster
+    Third,
+    /// Warlock: a few slots, all of one level, back on a short rest
+    Pact,
+}
+
+/// Slots per spell level (1st to 9th) for each full caster level
+const FULL_CASTER_SLOTS: [[usize; 9]; 20] = [
+    [2, 0, 0, 0, 0, 0, 0, 0, 0],
+    [3, 0, 0, 0, 0, 0, 0, 0, 0],
+    [4, 2, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 0, 0, 0, 0, 0, 0, 0],
+    [4, 3, 2, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 0, 0, 0, 0, 0, 0],
+    [4, 3, 3, 1, 0, 0, 0, 0, 0],
+    [4, 3, 3, 2, 0, 0, 0, 0, 0],
+    [4, 3, 3, 3, 1, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 0, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 0, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 0, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 0],
+    [4, 3, 3, 3, 2, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 1, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 1, 1, 1],
+    [4, 3, 3, 3, 3, 2, 2, 1, 1],
+];
+
+/// (slots, slot level) for each warlock level
+const PACT_SLOTS: [(usize, usize); 20] = [
+    (1, 1),
+    (2, 1),
+    (2, 2),
+    (2, 2),
+    (2, 3),
+    (2, 3),
+    (2, 4),
+    (2, 4),
+    (2, 5),
+    (2, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (3, 5),
+    (4, 5),
+    (4, 5),
+    (4, 5),
+    (4, 5),
+];
+
+impl CasterType {
+    /// (slot level, number of slots) at a class level
+    pub fn spell_slots(&self, level: usize) -> Vec<(usize, usize)> {
+        let level = level.clamp(1, 20);
+        if *self == CasterType::Pact {
+            let (slots, slot_level) = PACT_SLOTS[level - 1];
+            return vec![(slot_level, slots)];
+        }
+
+        let caster_level = match self {
+            CasterType::Full => level,
+            CasterType::Half if level >= 2 => level.div_ceil(2),
+            CasterType::Third if level >= 3 => level.div_ceil(3),
+            _ => return vec![],
+        };
+        FULL_CASTER_SLOTS[caster_level - 1]
+            .iter()
+            .enumerate()
+            .filter(|(_, slots)| **slots > 0)
+            .map(|(i, slots)| (i + 1, *slots))
+            .collect()
+    }
+
+    /// Pact slots come back on a short rest, everyone else's on a long rest
+    pub fn slot_recharge(&self) -> Rest {
+        match self {
+            CasterType::Pact => Rest::Short,
+            _ => Rest::Long,
+        }
+    }
+}
+
+/// A caster's spell attack bonus, save DC and spellcasting ability modifier
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spellcasting {
+    #[serde(default)]
+    pub caster: CasterType,
+    pub attack_bonus: isize,
+    pub save_dc: usize,
+    #[serde(default)]
+    pub modifier: isize,
+}
+
+impl Default for Spellcasting {
+    /// No proficiency and no ability bonus
+    fn default() -> Self {
+        Spellcasting {
+            caster: CasterType::Full,
+            attack_bonus: 0,
+            save_dc: 8,
+            modifier: 0,
+        }
+    }
+}
+
+/// Built-in spells, a selection from the SRD
+pub fn spell_list() -> Vec<Spell> {
+    let verbal_somatic = Components {
+        verbal: true,
+        somatic: true,
+        material: false,
+    };
+    let all_components = Components {
+        verbal: true,
+        somatic: true,
+        material: true,
+    };
+    let spell = |name: &str, level, effect| Spell {
+        name: name.into(),
+        level,
+        casting_time: CastingTime::Action,
+        components: verbal_somatic.clone(),
+        targets: 1,
+        effect,
+        upcast: Upcast::default(),
+        concentration: false,
+        damage_type: None,
+        range: Range::default(),
+    };
+    let upcast = |dice: &str, targets| Upcast {
+        dice: (!dice.is_empty()).then(|| dice.into()),
+        targets,
+    };
+
+    vec![
+        Spell {
+            range: Range::within(120),
+            damage_type: Some(DamageType::Fire),
+            ..spell(
+                "Fire Bolt",
+                0,
+                SpellEffect::Attack {
+                    damage: "1d10".into(),
+                    add_modifier: false,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            damage_type: Some(DamageType::Cold),
+            ..spell(
+                "Ray of Frost",
+                0,
+                SpellEffect::Attack {
+                    damage: "1d8".into(),
+                    add_modifier: false,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            damage_type: Some(DamageType::Radiant),
+            ..spell(
+                "Sacred Flame",
+                0,
+                SpellEffect::Save {
+                    ability: Ability::Dexterity,
+                    damage: "1d8".into(),
+                    half_on_success: false,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            damage_type: Some(DamageType::Thunder),
+            upcast: upcast("1d8", 0),
+            ..spell(
+                "Shatter",
+                2,
+                SpellEffect::Save {
+                    ability: Ability::Constitution,
+                    damage: "3d8".into(),
+                    half_on_success: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(120),
+            damage_type: Some(DamageType::Radiant),
+            upcast: upcast("1d6", 0),
+            ..spell(
+                "Guiding Bolt",
+                1,
+                SpellEffect::Attack {
+                    damage: "4d6".into(),
+                    add_modifier: false,
+                },
+            )
+        },
+        Spell {
+            damage_type: Some(DamageType::Necrotic),
+            upcast: upcast("1d10", 0),
+            ..spell(
+                "Inflict Wounds",
+                1,
+                SpellEffect::Attack {
+                    damage: "3d10".into(),
+                    add_modifier: false,
+                },
+            )
+        },
+        Spell {
+            upcast: upcast("1d8", 0),
+            ..spell(
+                "Cure Wounds",
+                1,
+                SpellEffect::Heal {
+                    healing: "1d8".into(),
+                    add_modifier: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            casting_time: CastingTime::BonusAction,
+            components: Components {
+                verbal: true,
+                somatic: false,
+                material: false,
+            },
+            upcast: upcast("1d4", 0),
+            ..spell(
+                "Healing Word",
+                1,
+                SpellEffect::Heal {
+                    healing: "1d4".into(),
+                    add_modifier: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            casting_time: CastingTime::BonusAction,
+            components: Components {
+                verbal: true,
+                somatic: false,
+                material: false,
+            },
+            targets: 6,
+            upcast: upcast("1d4", 0),
+            ..spell(
+                "Mass Healing Word",
+                3,
+                SpellEffect::Heal {
+                    healing: "1d4".into(),
+                    add_modifier: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(30),
+            casting_time: CastingTime::Minutes(10),
+            components: Components {
+                verbal: true,
+                somatic: false,
+                material: false,
+            },
+            targets: 6,
+            upcast: upcast("1d8", 0),
+            ..spell(
+                "Prayer of Healing",
+                2,
+                SpellEffect::Heal {
+                    healing: "2d8".into(),
+                    add_modifier: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            concentration: true,
+            upcast: upcast("", 1),
+            ..spell(
+                "Hold Person",
+                2,
+                SpellEffect::Condition {
+                    condition: ConditionType::Paralyzed,
+                    save: Some(Ability::Wisdom),
+                    rounds: 10,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            concentration: true,
+            ..spell(
+                "Dominate Person",
+                5,
+                SpellEffect::Condition {
+                    condition: ConditionType::Charmed,
+                    save: Some(Ability::Wisdom),
+                    rounds: 10,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(30),
+            components: all_components.clone(),
+            targets: 3,
+            upcast: upcast("", 1),
+            concentration: true,
+            ..spell(
+                "Bless",
+                1,
+                SpellEffect::Buff {
+                    effects: vec![
+                        Effect::AttackRolls { dice: "1d4".into() },
+                        Effect::SavingThrows { dice: "1d4".into() },
+                    ],
+                    rounds: 10,
+                    self_only: false,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(30),
+            components: all_components.clone(),
+            targets: 3,
+            upcast: upcast("", 1),
+            concentration: true,
+            ..spell(
+                "Bane",
+                1,
+                SpellEffect::Debuff {
+                    effects: vec![
+                        Effect::AttackRolls {
+                            dice: "-1d4".into(),
+                        },
+                        Effect::SavingThrows {
+                            dice: "-1d4".into(),
+                        },
+                    ],
+                    save: Some(Ability::Charisma),
+                    rounds: 10,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(60),
+            casting_time: CastingTime::BonusAction,
+            components: all_components.clone(),
+            concentration: true,
+            ..spell(
+                "Shield of Faith",
+                1,
+                SpellEffect::Buff {
+                    effects: vec![Effect::ArmorClass { bonus: 2 }],
+                    rounds: 100,
+                    self_only: false,
+                },
+            )
+        },
+        Spell {
+            casting_time: CastingTime::BonusAction,
+            components: all_components.clone(),
+            concentration: true,
+            ..spell(
+                "Divine Favor",
+                1,
+                SpellEffect::Buff {
+                    effects: vec![Effect::DamageOnHit { dice: "1d4".into() }],
+                    rounds: 10,
+                    self_only: true,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(90),
+            casting_time: CastingTime::BonusAction,
+            components: Components {
+                verbal: true,
+                somatic: false,
+                material: false,
+            },
+            concentration: true,
+            ..spell(
+                "Hunter's Mark",
+                1,
+                SpellEffect::Debuff {
+                    effects: vec![Effect::DamageFromSource { dice: "1d6".into() }],
+                    save: None,
+                    rounds: 600,
+                },
+            )
+        },
+        Spell {
+            range: Range::within(90),
+            casting_time: CastingTime::BonusAction,
+            components: all_components.clone(),
+            concentration: true,
+            ..spell(
+                "Hex",
+                1,
+                SpellEffect::Debuff {
+                    effects: vec![Effect::DamageFromSource { dice: "1d6".into() }],
+                    save: None,
+                    rounds: 600,
+                },
+            )
+        },
+        Spell {
+            components: all_components.clone(),
+            upcast: upcast("", 1),
+            ..spell(
+                "Longstrider",
+                1,
+                SpellEffect::Buff {
+                    effects: vec![Effect::Speed { change: 10 }],
+                    rounds: 600,
+                    self_only: false,
+                },
+            )
+        },
+    ]
+}
+
+#[cfg(test)]
+mod spell_tests {
+    use super::*;
+
+    #[test]
+    fn test_slot_tables() {
+        assert_eq!(CasterType::Full.spell_slots(1), vec![(1, 2)]);
+        assert_eq!(
+            CasterType::Full.spell_slots(5),
+            vec![(1, 4), (2, 3), (3, 2)]
+        );
+        assert_eq!(CasterType::Full.spell_slots(20).len(), 9);
+
+        assert!(CasterType::Half.spell_slots(1).is_empty());
+        assert_eq!(CasterType::Half.spell_slots(2), vec![(1, 2)]);
+        assert_eq!(CasterType::Half.spell_slots(5), vec![(1, 4), (2, 2)]);
+
+        assert!(CasterType::Third.spell_slots(2).is_empty());
+        assert_eq!(CasterType::Third.spell_slots(3), vec![(1, 2)]);
+        assert_eq!(CasterType::Third.spell_slots(7), vec![(1, 4), (2, 2)]);
+
+        assert_eq!(CasterType::Pact.spell_slots(1), vec![(1, 1)]);
+        assert_eq!(CasterType::Pact.spell_slots(5), vec![(3, 2)]);
+        assert_eq!(CasterType::Pact.spell_slots(17), vec![(5, 4)]);
+        assert_eq!(CasterType::Pact.slot_recharge(), Rest::Short);
+    }
+
+    #[test]
+    fn test_upcasting() {
+        let cure_wounds = Spell::named("cure wounds").unwrap();
+        assert_eq!(cure_wounds.dice_at(1, 3).to_string(), "1d8");
+        assert_eq!(cure_wounds.dice_at(3, 5).to_string(), "3d8");
+
+        let mass_healing_word = Spell::named("Mass Healing Word").unwrap();
+        assert_eq!(mass_healing_word.targets_at(3), 6);
+        assert_eq!(mass_healing_word.dice_at(4, 7).to_string(), "2d4");
+    }
+
+    #[test]
+    fn test_cantrip_scaling() {
+        let fire_bolt = Spell::named("Fire Bolt").unwrap();
+        assert_eq!(fire_bolt.dice_at(0, 1).to_string(), "1d10");
+        assert_eq!(fire_bolt.dice_at(0, 5).to_string(), "2d10");
+        assert_eq!(fire_bolt.dice_at(0, 11).to_string(), "3d10");
+        assert_eq!(fire_bolt.dice_at(0, 20).to_string(), "4d10");
+        // Slot level is irrelevant to cantrips
+        assert_eq!(fire_bolt.dice_at(3, 1).to_string(), "1d10");
+    }
+
+    #[test]
+    fn test_casting_time() {
+        assert!(Spell::named("Healing Word").unwrap().castable_in_combat());
+        assert!(!Spell::named("Prayer of Healing")
+            .unwrap()
+            .castable_in_combat());
+    }
+}
@@ -0,0 +1,17 @@
+/// Running totals for a single combatant over the course of one combat.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CombatantTally {
+    pub attacks: usize,
+    pub hits: usize, // includes critical hits
+    pub crits: usize,
+    pub kills: usize,
+    pub damage_dealt: usize,
+    pub damage_taken: usize,
+    pub healing_done: usize,
+    pub healing_received: usize,
+    pub resources_spent: usize,
+    pub turns_incapacitated: usize,
+    /// Concentration lost to damage or to going down
+    pub concentration_broken: usize,
+    pub downed_in_round: Option<usize>,
+}
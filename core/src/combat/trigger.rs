@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{Action, DicePool};
+
+use super::DamageType;
+
+/// Effects that go off on their own during the creature's turn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Trigger {
+    /// Regains hit points at the start of its turn, unless it took one of the
+    /// `suppressed_by` damage types since the start of its last turn
+    Regeneration {
+        healing: DicePool,
+        #[serde(default)]
+        suppressed_by: Vec<DamageType>,
+    },
+    /// `action` against every valid target at no cost. On a grid only
+    /// those within the action's range are caught in it.
+    Aura {
+        action: Action,
+        #[serde(default)]
+        at: TurnPoint,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TurnPoint {
+    #[default]
+    Start,
+    End,
+}
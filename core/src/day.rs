@@ -0,0 +1,282 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::Rest,
+    encounter::{CreatureSpec, Encounter, EncounterError},
+    Character, Team,
+};
+
+/// A party fighting several encounters in a row, keeping its HP and
+/// resources between them apart from whatever the rests in between restore.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AdventuringDay {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub heroes: Vec<CreatureSpec>,
+    #[serde(default)]
+    pub encounters: Vec<DayEncounter>,
+}
+
+/// One fight of the day. Takes every key an encounter file does, apart
+/// from `heroes` as the party comes from the day.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DayEncounter {
+    #[serde(flatten)]
+    pub encounter: Encounter,
+    /// The party is caught off guard in this fight
+    #[serde(default)]
+    pub party_surprised: bool,
+    /// Rest the party takes after winning this fight. `dawn` is a long rest
+    /// that runs into the next day.
+    #[serde(default)]
+    pub rest_after: Option<Rest>,
+}
+
+/// How one fight of a day went
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncounterResult {
+    pub heroes_won: bool,
+    pub rounds: usize,
+    /// Party state when the fight ended, before any rest
+    pub party_hp: usize,
+    pub heroes_standing: usize,
+    pub resources_remaining: usize,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DayResult {
+    /// One entry per encounter fought; the day stops at the first loss
+    pub encounters: Vec<EncounterResult>,
+}
+
+impl DayResult {
+    pub fn encounters_won(&self) -> usize {
+        self.encounters.iter().filter(|e| e.heroes_won).count()
+    }
+}
+
+impl AdventuringDay {
+    pub fn from_toml_str(s: &str) -> Result<Self, EncounterError> {
+        let day: AdventuringDay = toml::from_str(s)?;
+        day.heroes.iter().try_for_each(CreatureSpec::validate)?;
+        for encounter in &day.encounters {
+            if !encounter.encounter.heroes.is_empty() {
+                return Err(EncounterError::InvalidParameter(
+                    "heroes belong to the day, not to one of its encounters".into(),
+                ));
+            }
+            encounter.encounter.validate()?;
+        }
+        Ok(day)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EncounterError> {
+        AdventuringDay::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// Display name of each encounter, falling back to its position
+    pub fn encounter_names(&self) -> Vec<String> {
+        self.encounters
+            .iter()
+            .enumerate()
+            .map(|(i, encounter)| {
+                encounter
+                    .encounter
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| format!("Encounter {}", i + 1))
+            })
+            .collect()
+    }
+
+    /// Plays out the day once. Heroes downed in a fight the party wins are
+    /// stabilised at 1 HP before resting.
+    pub fn run(&self) -> DayResult {
+        let mut heroes: Vec<Character> = self
+            .heroes
+            .iter()
+            .flat_map(|spec| spec.build(Team::Heroes))
+            .collect();
+        let starting_positions: Vec<_> = heroes
+            .iter()
+            .map(|hero| (hero.position, hero.zone))
+            .collect();
+        let mut result = DayResult::default();
+
+        for encounter in &self.encounters {
+            // Each fight starts the party back where its specs put them
+            for (hero, (position, zone)) in heroes.iter_mut().zip(&starting_positions) {
+                hero.position = *position;
+                hero.zone = *zone;
+                hero.surprised = encounter.party_surprised;
+                hero.rally();
+            }
+            let mut combat = encounter.encounter.build_combat_with(heroes);
+            combat.run();
+
+            let heroes_won = combat.heroes_won();
+            let rounds = combat.rounds_fought();
+            heroes = combat.heroes;
+            // Summons don't outlast the fight
+            heroes.retain(|hero| hero.leaves_after_round.is_none() && hero.summoned_with.is_none());
+            result.encounters.push(EncounterResult {
+                heroes_won,
+                rounds,
+                party_hp: heroes.iter().map(|h| h.current_hp).sum(),
+                heroes_standing: heroes.iter().filter(|h| h.current_hp > 0).count(),
+                resources_remaining: heroes.iter().map(|h| h.resources_remaining()).sum(),
+            });
+            if !heroes_won {
+                break;
+            }
+
+            for hero in heroes.iter_mut() {
+                hero.current_hp = hero.current_hp.max(1);
+                match encounter.rest_after {
+                    Some(Rest::Short) => hero.short_rest(),
+                    Some(Rest::Long) => hero.long_rest(),
+                    Some(Rest::Dawn) => {
+                        hero.long_rest();
+                        hero.dawn();
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod day_tests {
+    use super::*;
+
+    #[test]
+    fn test_example_day() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-1-day.toml"
+        );
+        let day = AdventuringDay::load(path).unwrap();
+        assert_eq!(day.encounters.len(), 4);
+        assert_eq!(day.encounter_names()[0], "Kobold scouts");
+
+        for _ in 0..20 {
+            let result = day.run();
+            assert!(!result.encounters.is_empty());
+            assert!(result.encounters.len() <= 4);
+            // Only the last fight fought can be a loss
+            let last = result.encounters.len() - 1;
+            assert!(result.encounters[..last].iter().all(|e| e.heroes_won));
+            if result.encounters.len() < 4 {
+                assert!(!result.encounters[last].heroes_won);
+            }
+        }
+    }
+
+    #[test]
+    fn test_damage_carries_over() {
+        let day = AdventuringDay::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Fighter"
+            hp = 200
+            ac = 10
+            actions = [{ type = "attack", name = "Club", hit_bonus = 20, damage = "100" }]
+
+            [[encounters]]
+            monsters = [{ name = "Goblin", hp = 300, ac = 30, actions = [{ type = "attack", name = "Stick", hit_bonus = 20, damage = "10" }] }]
+
+            [[encounters]]
+            monsters = [{ name = "Rat", hp = 1, ac = 1 }]
+            "#,
+        )
+        .unwrap();
+
+        let result = day.run();
+        assert_eq!(result.encounters_won(), 2);
+        // The goblin gets some hits in and there's no rest to heal them
+        // before the rat
+        let first = &result.encounters[0];
+        assert!(first.party_hp < 200);
+        assert_eq!(result.encounters[1].party_hp, first.party_hp);
+    }
+
+    #[test]
+    fn test_encounter_objectives() {
+        let day = AdventuringDay::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Fighter"
+            hp = 20
+            ac = 10
+            actions = [{ type = "attack", name = "Club", hit_bonus = 20, damage = "100" }]
+
+            [[encounters]]
+            objective = { type = "kill", target = "Boss" }
+            monsters = [
+                { name = "Boss", hp = 1, ac = 1 },
+                { name = "Ogre", hp = 1000000, ac = 1 },
+            ]
+
+            [[encounters]]
+            monsters = [{ name = "Rat", hp = 1, ac = 1 }]
+            "#,
+        )
+        .unwrap();
+        assert!(day.encounters[0].encounter.objective.is_some());
+
+        // The ogre is still standing but the boss is down
+        let result = day.run();
+        assert_eq!(result.encounters_won(), 2);
+
+        let result = AdventuringDay::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Fighter"
+            hp = 20
+            ac = 10
+
+            [[encounters]]
+            objective = { type = "kill", target = "Nobody" }
+            monsters = [{ name = "Rat", hp = 1, ac = 1 }]
+            "#,
+        );
+        assert!(matches!(result, Err(EncounterError::UnknownCreature(_))));
+    }
+
+    #[test]
+    fn test_stalemate_ends_the_day() {
+        let day = AdventuringDay::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Fighter"
+            hp = 20
+            ac = 10
+
+            [[encounters]]
+            stalemate_rounds = 2
+            monsters = [{ name = "Rat", hp = 1, ac = 1 }]
+
+            [[encounters]]
+            monsters = [{ name = "Rat", hp = 1, ac = 1 }]
+            "#,
+        )
+        .unwrap();
+
+        // Nobody can hurt anybody, so the first fight is a draw
+        let result = day.run();
+        assert_eq!(result.encounters.len(), 1);
+        assert!(!result.encounters[0].heroes_won);
+        assert_eq!(result.encounters[0].rounds, 2);
+
+        let mut day = day;
+        day.encounters[0].encounter.stalemate_rounds = None;
+        day.encounters[0].encounter.max_rounds = Some(4);
+        assert_eq!(day.run().encounters[0].rounds, 4);
+    }
+}
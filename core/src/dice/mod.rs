@@ -1,4 +1,6 @@
 pub mod pool;
+pub mod rng;
 pub mod roll;
 
 pub use pool::{roll_dice, DicePool};
+pub use rng::{seed_rng, with_rng};
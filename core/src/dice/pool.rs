@@ -1,10 +1,14 @@
 use std::cell::RefCell;
 
 use rand::Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
 use crate::AdvantageType;
 
-use super::roll::{DiceRollResult, RollResult};
+use super::{
+    rng::with_rng,
+    roll::{DiceRollResult, RollResult},
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct DicePool {
@@ -45,19 +49,36 @@ impl DicePool {
     }
 
     pub fn critical_hit(&self) -> Self {
-        let mut pool = vec![];
-        for d in &self.dice {
-            pool.push(Dice::new(d.count * 2, d.sides));
-        }
+        self.multiply_dice(2)
+    }
 
+    /// Every die rolled `factor` times over, keeping the modifier. Used for
+    /// critical hits and cantrip scaling.
+    pub fn multiply_dice(&self, factor: usize) -> Self {
         DicePool {
-            dice: pool,
+            dice: self
+                .dice
+                .iter()
+                .map(|d| Dice {
+                    count: d.count * factor,
+                    ..d.clone()
+                })
+                .collect(),
             modifier: self.modifier,
             last_roll: RefCell::new(None),
             advantage_type: AdvantageType::None,
         }
     }
 
+    /// Adds `times` copies of another pool's dice and modifier
+    pub fn add_pool(mut self, other: &DicePool, times: usize) -> Self {
+        for _ in 0..times {
+            self.dice.extend(other.dice.iter().cloned());
+            self.modifier += other.modifier;
+        }
+        self
+    }
+
     pub fn add_dice(mut self, count: usize, sides: usize) -> Self {
         self.dice.push(Dice::new(count, sides));
         self
@@ -132,6 +153,7 @@ impl DicePool {
         total
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str<T: Into<String>>(s: T) -> Result<Self, String> {
         let s = s.into();
         let mut pool = DicePool::new();
@@ -171,6 +193,23 @@ impl DicePool {
         Ok(pool)
     }
 
+    /// Expected total of a roll, ignoring advantage
+    pub fn average(&self) -> f64 {
+        let dice: f64 = self
+            .dice
+            .iter()
+            .map(|d| {
+                let average = d.count as f64 * (d.sides as f64 + 1.0) / 2.0;
+                if d.negative {
+                    -average
+                } else {
+                    average
+                }
+            })
+            .sum();
+        dice + self.modifier as f64
+    }
+
     pub fn count_dice(&self, sides: usize) -> usize {
         self.dice
             .iter()
@@ -181,10 +220,7 @@ impl DicePool {
 
     pub fn debug_last_roll(&self) -> Option<RollResult> {
         let last_roll = self.last_roll.borrow();
-        match *last_roll {
-            None => None,
-            Some(ref last_roll) => Some(last_roll.clone()),
-        }
+        (*last_roll).clone()
     }
 }
 
@@ -194,6 +230,7 @@ impl From<&str> for DicePool {
     }
 }
 
+#[allow(clippy::to_string_trait_impl)]
 impl ToString for DicePool {
     fn to_string(&self) -> String {
         let mut parts = Vec::new();
@@ -207,7 +244,7 @@ impl ToString for DicePool {
 
         // Sort by dice sides for consistent output
         let mut sides: Vec<_> = dice_map.keys().collect();
-        sides.sort_by(|a, b| b.0.cmp(&a.0));
+        sides.sort_by_key(|s| std::cmp::Reverse(s.0));
 
         for &(side, negative) in sides {
             if let Some(&count) = dice_map.get(&(side, negative)) {
@@ -237,6 +274,20 @@ impl ToString for DicePool {
     }
 }
 
+// Dice pools are written as dice strings ("2d6+3") in encounter files
+impl Serialize for DicePool {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for DicePool {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DicePool::from_str(s).map_err(de::Error::custom)
+    }
+}
+
 impl From<String> for DicePool {
     fn from(s: String) -> Self {
         Self::from_str(&s).unwrap_or_default()
@@ -260,15 +311,16 @@ impl Dice {
     }
 
     fn roll(&self) -> isize {
-        let mut rng = rand::thread_rng();
-        let sum: usize = (0..self.count).map(|_| rng.gen_range(1..=self.sides)).sum();
+        let sum: usize =
+            with_rng(|rng| (0..self.count).map(|_| rng.gen_range(1..=self.sides)).sum());
         if self.negative {
-            sum as isize * -1
+            -(sum as isize)
         } else {
             sum as isize
         }
     }
 
+    #[allow(clippy::inherent_to_string)]
     fn to_string(&self) -> String {
         let mut parts = vec![];
         if self.negative {
@@ -0,0 +1,38 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, SeedableRng};
+
+thread_local! {
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Reseeds this thread's dice so the following rolls (and every random choice
+/// the combat engine makes) are reproducible.
+pub fn seed_rng(seed: u64) {
+    RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+/// Runs `f` with this thread's RNG. All randomness in the simulator should go
+/// through here so that seeding covers it.
+pub fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+#[cfg(test)]
+mod rng_tests {
+    use super::*;
+    use crate::roll_dice;
+
+    #[test]
+    fn test_seeded_rolls_repeat() {
+        seed_rng(1234);
+        let first: Vec<isize> = (0..20).map(|_| roll_dice("1d20")).collect();
+        seed_rng(1234);
+        let second: Vec<isize> = (0..20).map(|_| roll_dice("1d20")).collect();
+        assert_eq!(first, second);
+
+        seed_rng(4321);
+        let third: Vec<isize> = (0..20).map(|_| roll_dice("1d20")).collect();
+        assert_ne!(first, third);
+    }
+}
@@ -1,18 +1,9 @@
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Default)]
 pub struct RollResult {
     pub rolls: Vec<DiceRollResult>,
     pub modifier: isize,
 }
 
-impl Default for RollResult {
-    fn default() -> Self {
-        RollResult {
-            rolls: vec![],
-            modifier: 0,
-        }
-    }
-}
-
 impl RollResult {
     pub fn new() -> Self {
         RollResult::default()
@@ -0,0 +1,312 @@
+use rand::seq::SliceRandom;
+
+use crate::{
+    encounter::{CreatureSpec, Encounter, EncounterError},
+    with_rng,
+};
+
+use super::{
+    generate::{hero_for_level, CHALLENGE_RATINGS},
+    simulated::{simulate_difficulty, SimulatedOutcome},
+    TableCalculator,
+};
+
+/// The inverse of a difficulty calculator: finds groups of monsters from a
+/// bestiary that a budget table rates at the requested difficulty for a
+/// party.
+pub struct EncounterBuilder {
+    party: Vec<usize>,
+    bestiary: Vec<CreatureSpec>,
+    calculator: TableCalculator,
+    target: &'static str,
+    max_monsters: usize,
+    max_kinds: usize,
+}
+
+/// Monsters the builder settled on, with counts
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub monsters: Vec<CreatureSpec>,
+    pub rating: &'static str,
+}
+
+impl EncounterBuilder {
+    pub fn new(
+        party: Vec<usize>,
+        bestiary: Vec<CreatureSpec>,
+        calculator: TableCalculator,
+        difficulty: &str,
+    ) -> Result<Self, EncounterError> {
+        let target = calculator.rating(difficulty).ok_or_else(|| {
+            EncounterError::InvalidParameter(format!(
+                "{} doesn't have a {:?} difficulty, pick one of {}",
+                calculator.description(),
+                difficulty,
+                calculator.ratings().join(", ")
+            ))
+        })?;
+        if party.is_empty() || party.iter().any(|level| !(1..=20).contains(level)) {
+            return Err(EncounterError::InvalidParameter(
+                "party levels must be between 1 and 20".into(),
+            ));
+        }
+        if bestiary.is_empty() {
+            return Err(EncounterError::InvalidParameter(
+                "the bestiary has no monsters".into(),
+            ));
+        }
+        if let Some(monster) = bestiary
+            .iter()
+            .find(|m| !CHALLENGE_RATINGS.contains(&m.challenge_rating))
+        {
+            return Err(EncounterError::InvalidParameter(format!(
+                "{} has CR {}, which isn't a standard challenge rating",
+                monster.name, monster.challenge_rating
+            )));
+        }
+
+        Ok(EncounterBuilder {
+            party,
+            bestiary,
+            calculator,
+            target,
+            max_monsters: 8,
+            max_kinds: 2,
+        })
+    }
+
+    /// Most monsters in one candidate (default 8)
+    pub fn with_max_monsters(mut self, max_monsters: usize) -> Self {
+        self.max_monsters = max_monsters;
+        self
+    }
+
+    /// Most different kinds of monster in one candidate (default 2)
+    pub fn with_max_kinds(mut self, max_kinds: usize) -> Self {
+        self.max_kinds = max_kinds;
+        self
+    }
+
+    /// Every combination within the limits that rates at the target
+    /// difficulty, fewest kinds of monster first. For the calculator's
+    /// hardest rating only combinations that are just over its threshold
+    /// count.
+    pub fn candidates(&self) -> Vec<Candidate> {
+        let mut candidates = vec![];
+        let mut counts = vec![0; self.bestiary.len()];
+        self.search(0, &mut counts, &mut candidates);
+        candidates.sort_by_key(|c| c.monsters.len());
+        candidates
+    }
+
+    /// Up to `n` matching candidates picked at random
+    pub fn build(&self, n: usize) -> Vec<Candidate> {
+        let mut candidates = self.candidates();
+        with_rng(|rng| candidates.shuffle(rng));
+        candidates.truncate(n);
+        candidates.sort_by_key(|c| c.monsters.len());
+        candidates
+    }
+
+    /// Pits generic heroes of the party's levels against a candidate
+    pub fn encounter(&self, candidate: &Candidate) -> Encounter {
+        let heroes = self
+            .party
+            .iter()
+            .enumerate()
+            .map(|(i, level)| hero_for_level(format!("Hero {}", i + 1), *level))
+            .collect();
+        Encounter::new(heroes, candidate.monsters.clone())
+    }
+
+    /// Simulates a candidate against generic heroes to check the table's
+    /// rating
+    pub fn verify(&self, candidate: &Candidate, iterations: usize) -> SimulatedOutcome {
+        simulate_difficulty(&self.encounter(candidate), iterations)
+    }
+
+    fn search(&self, index: usize, counts: &mut [usize], candidates: &mut Vec<Candidate>) {
+        if index == self.bestiary.len() {
+            if counts.iter().any(|c| *c > 0) {
+                self.check(counts, candidates);
+            }
+            return;
+        }
+
+        let total: usize = counts.iter().sum();
+        let kinds = counts.iter().filter(|c| **c > 0).count();
+        let max_count = if kinds < self.max_kinds {
+            self.max_monsters - total
+        } else {
+            0
+        };
+        for count in 0..=max_count {
+            counts[index] = count;
+            self.search(index + 1, counts, candidates);
+        }
+        counts[index] = 0;
+    }
+
+    fn rate(&self, counts: &[usize]) -> String {
+        let monster_crs: Vec<f64> = self
+            .bestiary
+            .iter()
+            .zip(counts)
+            .flat_map(|(monster, count)| vec![monster.challenge_rating; *count])
+            .collect();
+        self.calculator
+            .rate(&self.party, &monster_crs)
+            .expect("the party and bestiary are checked in new")
+    }
+
+    fn check(&self, counts: &[usize], candidates: &mut Vec<Candidate>) {
+        if self.rate(counts) != self.target {
+            return;
+        }
+        // The hardest rating has no upper bound, so only keep encounters
+        // that would drop out of it if any one monster were taken away
+        if self.calculator.ratings().last() == Some(&self.target) {
+            let mut fewer = counts.to_vec();
+            for i in 0..fewer.len() {
+                if fewer[i] == 0 {
+                    continue;
+                }
+                fewer[i] -= 1;
+                let still_target = self.rate(&fewer) == self.target;
+                fewer[i] += 1;
+                if still_target {
+                    return;
+                }
+            }
+        }
+
+        let monsters = self
+            .bestiary
+            .iter()
+            .zip(counts)
+            .filter(|(_, count)| **count > 0)
+            .map(|(monster, count)| CreatureSpec {
+                count: *count,
+                ..monster.clone()
+            })
+            .collect();
+        candidates.push(Candidate {
+            monsters,
+            rating: self.target,
+        });
+    }
+}
+
+#[cfg(test)]
+mod builder_tests {
+    use super::*;
+    use crate::dnd::generate::monster_for_cr;
+
+    fn bestiary() -> Vec<CreatureSpec> {
+        vec![
+            monster_for_cr("Small", 0.25),
+            monster_for_cr("Medium", 1.0),
+            monster_for_cr("Large", 3.0),
+        ]
+    }
+
+    #[test]
+    fn test_candidates_match_target() {
+        let builder = EncounterBuilder::new(
+            vec![3, 3, 4, 4],
+            bestiary(),
+            TableCalculator::Wizards2014,
+            "hard",
+        )
+        .unwrap();
+        let candidates = builder.candidates();
+        assert!(!candidates.is_empty());
+
+        for candidate in &candidates {
+            assert_eq!(candidate.rating, "Hard");
+            assert!(candidate.monsters.len() <= 2);
+            let crs: Vec<f64> = candidate
+                .monsters
+                .iter()
+                .flat_map(|m| vec![m.challenge_rating; m.count])
+                .collect();
+            assert!(crs.len() <= 8);
+            assert_eq!(
+                TableCalculator::Wizards2014
+                    .rate(&[3, 3, 4, 4], &crs)
+                    .unwrap(),
+                "Hard"
+            );
+        }
+
+        assert_eq!(builder.build(3).len(), 3);
+
+        // 2014 names map onto the 2024 ratings
+        let builder = EncounterBuilder::new(
+            vec![3, 3, 4, 4],
+            bestiary(),
+            TableCalculator::Wizards2024,
+            "hard",
+        )
+        .unwrap();
+        let candidates = builder.candidates();
+        assert!(!candidates.is_empty());
+        assert!(candidates.iter().all(|c| c.rating == "High"));
+
+        // High is open-ended, so every candidate is only just High
+        for candidate in &candidates {
+            let crs: Vec<f64> = candidate
+                .monsters
+                .iter()
+                .flat_map(|m| vec![m.challenge_rating; m.count])
+                .collect();
+            for i in 0..crs.len() {
+                let mut fewer = crs.clone();
+                fewer.remove(i);
+                assert_ne!(
+                    TableCalculator::Wizards2024
+                        .rate(&[3, 3, 4, 4], &fewer)
+                        .unwrap(),
+                    "High"
+                );
+            }
+        }
+        let encounter = builder.encounter(&candidates[0]);
+        assert_eq!(encounter.hero_levels(), vec![3, 3, 4, 4]);
+    }
+
+    #[test]
+    fn test_example_bestiary() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../encounters/bestiary.toml");
+        let bestiary = Encounter::load(path).unwrap().monsters;
+        let builder = EncounterBuilder::new(
+            vec![1, 1, 1, 1],
+            bestiary,
+            TableCalculator::Mcdm,
+            "standard",
+        )
+        .unwrap();
+        assert!(!builder.candidates().is_empty());
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let unknown_rating =
+            EncounterBuilder::new(vec![1], bestiary(), TableCalculator::Wizards2024, "brutal");
+        assert!(matches!(
+            unknown_rating,
+            Err(EncounterError::InvalidParameter(_))
+        ));
+
+        let mut odd_cr = bestiary();
+        odd_cr[0].challenge_rating = 0.3;
+        assert!(EncounterBuilder::new(vec![1], odd_cr, TableCalculator::Mcdm, "hard").is_err());
+        assert!(
+            EncounterBuilder::new(vec![21], bestiary(), TableCalculator::Mcdm, "hard").is_err()
+        );
+
+        assert!(TableCalculator::Wizards2014.rate(&[1], &[0.3]).is_err());
+        assert!(TableCalculator::Wizards2024.rate(&[0], &[1.0]).is_err());
+        assert!(TableCalculator::Mcdm.rate(&[21], &[1.0]).is_err());
+    }
+}
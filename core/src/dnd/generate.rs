@@ -0,0 +1,199 @@
+use phf::phf_map;
+
+use crate::{
+    battlefield::Range,
+    combat::{ResourceType, Rest},
+    encounter::{CreatureSpec, Encounter, ResourceSpec},
+    Action, DicePool,
+};
+
+use super::proficiency_bonus;
+
+/// (AC, hit points, attack bonus, damage per round) by challenge rating,
+/// from the "Monster Statistics by Challenge Rating" table in the 2014 DMG.
+/// Hit points and damage are the midpoints of the listed ranges.
+static MONSTER_STATS_BY_CR: phf::Map<&'static str, (usize, usize, isize, usize)> = phf_map! {
+    "0" => (13, 4, 3, 1),
+    "0.125" => (13, 21, 3, 3),
+    "0.25" => (13, 43, 3, 5),
+    "0.5" => (13, 60, 3, 7),
+    "1" => (13, 78, 3, 12),
+    "2" => (13, 93, 3, 18),
+    "3" => (13, 108, 4, 24),
+    "4" => (14, 123, 5, 30),
+    "5" => (15, 138, 6, 36),
+    "6" => (15, 153, 6, 42),
+    "7" => (15, 168, 6, 48),
+    "8" => (16, 183, 7, 54),
+    "9" => (16, 198, 7, 60),
+    "10" => (17, 213, 7, 66),
+    "11" => (17, 228, 8, 72),
+    "12" => (17, 243, 8, 78),
+    "13" => (18, 258, 8, 84),
+    "14" => (18, 273, 8, 90),
+    "15" => (18, 288, 8, 96),
+    "16" => (18, 303, 9, 102),
+    "17" => (19, 318, 10, 108),
+    "18" => (19, 333, 10, 114),
+    "19" => (19, 348, 10, 120),
+    "20" => (19, 378, 10, 132),
+    "21" => (19, 423, 11, 150),
+    "22" => (19, 468, 11, 168),
+    "23" => (19, 513, 11, 186),
+    "24" => (19, 558, 12, 204),
+    "25" => (19, 603, 12, 222),
+    "26" => (19, 648, 12, 240),
+    "27" => (19, 693, 13, 258),
+    "28" => (19, 738, 13, 276),
+    "29" => (19, 783, 13, 294),
+    "30" => (19, 828, 14, 312),
+};
+
+/// Every challenge rating with an entry in the monster tables
+pub const CHALLENGE_RATINGS: [f64; 34] = [
+    0.0, 0.125, 0.25, 0.5, 1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0, 10.0, 11.0, 12.0, 13.0,
+    14.0, 15.0, 16.0, 17.0, 18.0, 19.0, 20.0, 21.0, 22.0, 23.0, 24.0, 25.0, 26.0, 27.0, 28.0, 29.0,
+    30.0,
+];
+
+/// A generic monster with the DMG's expected statistics for `cr`, dealing
+/// its damage per round as a single attack. Panics on a CR that isn't in
+/// the table.
+pub fn monster_for_cr<T: Into<String>>(name: T, cr: f64) -> CreatureSpec {
+    let (ac, hp, attack_bonus, damage) = *MONSTER_STATS_BY_CR
+        .get(cr.to_string().as_str())
+        .unwrap_or_else(|| panic!("no monster statistics for CR {}", cr));
+
+    let mut spec = CreatureSpec::new(name, hp, ac);
+    spec.challenge_rating = cr;
+    spec.initiative_bonus = 1;
+    spec.actions = vec![Action::Attack {
+        name: "Attack".into(),
+        hit_bonus: attack_bonus,
+        damage: damage_dice(damage),
+        damage_type: None,
+        range: Range::default(),
+    }];
+    spec
+}
+
+/// A generic martial hero of the given level: d10 hit dice with a +2 CON,
+/// chain mail and a shield, a +3 attack stat, an extra weapon die at 5th,
+/// 11th and 20th level and Second Wind once per short rest.
+pub fn hero_for_level<T: Into<String>>(name: T, level: usize) -> CreatureSpec {
+    let level = level.clamp(1, 20);
+    let attacks = 1 + [5, 11, 20].iter().filter(|l| level >= **l).count();
+
+    let mut spec = CreatureSpec::new(name, 12 + 8 * (level - 1), 18);
+    spec.level = level;
+    spec.initiative_bonus = 1;
+    spec.actions = vec![
+        Action::Attack {
+            name: "Longsword".into(),
+            hit_bonus: proficiency_bonus(level) + 3,
+            damage: DicePool::new()
+                .add_dice(attacks, 8)
+                .add_modifier(3 * attacks as isize),
+            damage_type: None,
+            range: Range::default(),
+        },
+        Action::SecondWind {
+            healing: DicePool::new().add_dice(1, 10).add_modifier(level as isize),
+            required_resources: vec![(ResourceType::Feature("Second Wind".into()), 1)],
+        },
+    ];
+    spec.resources = vec![ResourceSpec {
+        resource: ResourceType::Feature("Second Wind".into()),
+        max: 1,
+        recharge: Rest::Short,
+        recharge_dice: None,
+        recharge_roll: None,
+    }];
+    spec.hit_die = Some(DicePool::new().add_dice(1, 10).add_modifier(2));
+    spec
+}
+
+/// Builds a stand-in encounter out of generic heroes and monsters for a
+/// list of party levels and monster CRs
+pub fn generated_encounter(pc_levels: &[usize], monster_crs: &[f64]) -> Encounter {
+    Encounter::new(
+        group(pc_levels, |level, count| {
+            let mut spec = hero_for_level(format!("Level {} Hero", level), *level);
+            spec.count = count;
+            spec
+        }),
+        group(monster_crs, |cr, count| {
+            let mut spec = monster_for_cr(format!("CR {} Monster", cr), *cr);
+            spec.count = count;
+            spec
+        }),
+    )
+}
+
+/// Collapses equal values into one spec each, keeping first-seen order
+fn group<T: PartialEq>(
+    values: &[T],
+    spec: impl Fn(&T, usize) -> CreatureSpec,
+) -> Vec<CreatureSpec> {
+    let mut groups: Vec<(&T, usize)> = vec![];
+    for value in values {
+        match groups.iter_mut().find(|(v, _)| *v == value) {
+            Some((_, count)) => *count += 1,
+            None => groups.push((value, 1)),
+        }
+    }
+    groups
+        .into_iter()
+        .map(|(v, count)| spec(v, count))
+        .collect()
+}
+
+/// d6s plus a flat modifier averaging roughly `average` damage
+fn damage_dice(average: usize) -> DicePool {
+    if average < 4 {
+        return DicePool::new().add_dice(1, (2 * average).saturating_sub(2).max(2));
+    }
+    let dice = (average / 7).max(1);
+    let modifier = average as isize - (dice as f64 * 3.5).round() as isize;
+    DicePool::new().add_dice(dice, 6).add_modifier(modifier)
+}
+
+#[cfg(test)]
+mod generate_tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_encounter() {
+        let encounter = generated_encounter(&[3, 3, 4], &[1.0, 0.5, 1.0]);
+        assert_eq!(encounter.hero_levels(), vec![3, 3, 4]);
+        assert_eq!(encounter.monster_crs(), vec![1.0, 1.0, 0.5]);
+
+        let combat = encounter.build_combat();
+        assert_eq!(combat.heroes.len(), 3);
+        assert_eq!(combat.monsters.len(), 3);
+        assert_eq!(combat.heroes[0].name, "Level 3 Hero 1");
+        assert_eq!(combat.monsters[2].name, "CR 0.5 Monster");
+    }
+
+    #[test]
+    fn test_every_cr_has_stats() {
+        for cr in CHALLENGE_RATINGS {
+            let monster = monster_for_cr("Monster", cr);
+            assert_eq!(monster.challenge_rating, cr);
+        }
+    }
+
+    #[test]
+    fn test_damage_dice_average() {
+        for average in [1, 3, 5, 12, 36, 132, 312] {
+            let pool = damage_dice(average);
+            let expected = pool.average();
+            assert!(
+                (expected - average as f64).abs() <= 1.0,
+                "{} averages {}",
+                pool.to_string(),
+                expected
+            );
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use phf::phf_map;
 
-use super::DifficultyCalculatorImpl;
+use super::{level_entry, DifficultyCalculatorImpl};
+use crate::encounter::EncounterError;
 
 static EASY_CR_PER_LEVEL: phf::Map<&'static str, f64> = phf_map! {
     "1" => 0.125,
@@ -119,17 +120,19 @@ pub struct MCDMDifficultyCalculator;
 impl DifficultyCalculatorImpl for MCDMDifficultyCalculator {
     type DifficultyResult = MCDMDifficultyScale;
 
-    fn calculate(pc_levels: &Vec<usize>, monster_crs: &Vec<f64>) -> Self::DifficultyResult {
+    fn calculate(
+        pc_levels: &Vec<usize>,
+        monster_crs: &Vec<f64>,
+    ) -> Result<Self::DifficultyResult, EncounterError> {
         let total_cr: f64 = monster_crs.iter().sum();
         let mut easy_budget = 0.0;
         let mut hard_budget = 0.0;
         let mut standard_budget = 0.0;
 
         for i in pc_levels {
-            let i_str = i.to_string();
-            easy_budget += EASY_CR_PER_LEVEL.get(&i_str.as_str()).unwrap();
-            standard_budget += STANDARD_CR_PER_LEVEL.get(&i_str.as_str()).unwrap();
-            hard_budget += HARD_CR_PER_LEVEL.get(&i_str.as_str()).unwrap();
+            easy_budget += level_entry(&EASY_CR_PER_LEVEL, *i)?;
+            standard_budget += level_entry(&STANDARD_CR_PER_LEVEL, *i)?;
+            hard_budget += level_entry(&HARD_CR_PER_LEVEL, *i)?;
         }
 
         // under easy is trivial
@@ -137,15 +140,15 @@ impl DifficultyCalculatorImpl for MCDMDifficultyCalculator {
         // between easy and standard
 
         if total_cr < easy_budget {
-            MCDMDifficultyScale::Trivial
+            Ok(MCDMDifficultyScale::Trivial)
         } else if total_cr < standard_budget {
-            MCDMDifficultyScale::Easy
+            Ok(MCDMDifficultyScale::Easy)
         } else if total_cr < hard_budget {
-            MCDMDifficultyScale::Standard
+            Ok(MCDMDifficultyScale::Standard)
         } else if total_cr == hard_budget {
-            MCDMDifficultyScale::Hard
+            Ok(MCDMDifficultyScale::Hard)
         } else {
-            MCDMDifficultyScale::Extreme
+            Ok(MCDMDifficultyScale::Extreme)
         }
     }
 }
@@ -1,12 +1,49 @@
 use std::marker::PhantomData;
 
+use mcdm::MCDMDifficultyCalculator;
+use phf::Map;
+use wizards::{WizardDifficultyCalculator2014, WizardDifficultyCalculator2024};
+
+use crate::encounter::EncounterError;
+
+pub mod builder;
+pub mod generate;
 pub mod mcdm;
+pub mod simulated;
 pub mod wizards;
 
+pub fn proficiency_bonus(level: usize) -> isize {
+    2 + (level.max(1) as isize - 1) / 4
+}
+
 pub trait DifficultyCalculatorImpl {
     type DifficultyResult: Into<String>;
 
-    fn calculate(pc_levels: &Vec<usize>, monster_crs: &Vec<f64>) -> Self::DifficultyResult;
+    #[allow(clippy::ptr_arg)]
+    fn calculate(
+        pc_levels: &Vec<usize>,
+        monster_crs: &Vec<f64>,
+    ) -> Result<Self::DifficultyResult, EncounterError>;
+}
+
+/// The entry for a party member's level in one of the per-level tables
+fn level_entry<V: Copy>(table: &Map<&'static str, V>, level: usize) -> Result<V, EncounterError> {
+    table
+        .get(level.to_string().as_str())
+        .copied()
+        .ok_or_else(|| {
+            EncounterError::InvalidParameter(format!(
+                "party level {} isn't between 1 and 20",
+                level
+            ))
+        })
+}
+
+/// The entry for a monster's CR in one of the per-CR tables
+fn cr_entry<V: Copy>(table: &Map<&'static str, V>, cr: f64) -> Result<V, EncounterError> {
+    table.get(cr.to_string().as_str()).copied().ok_or_else(|| {
+        EncounterError::InvalidParameter(format!("CR {} isn't a standard challenge rating", cr))
+    })
 }
 
 pub struct DifficultyCalculator<T: DifficultyCalculatorImpl> {
@@ -24,7 +61,81 @@ impl<T: DifficultyCalculatorImpl> DifficultyCalculator<T> {
         }
     }
 
-    pub fn calculate(&self) -> T::DifficultyResult {
+    pub fn calculate(&self) -> Result<T::DifficultyResult, EncounterError> {
         T::calculate(&self.pc_levels, &self.monster_crs)
     }
 }
+
+/// The budget-table calculators, for picking one at runtime
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableCalculator {
+    Mcdm,
+    Wizards2014,
+    Wizards2024,
+}
+
+impl TableCalculator {
+    pub const ALL: [TableCalculator; 3] = [
+        TableCalculator::Mcdm,
+        TableCalculator::Wizards2014,
+        TableCalculator::Wizards2024,
+    ];
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            TableCalculator::Mcdm => "MCDM",
+            TableCalculator::Wizards2014 => "Wizards 2014",
+            TableCalculator::Wizards2024 => "Wizards 2024",
+        }
+    }
+
+    /// Every rating the calculator can give, easiest first
+    pub fn ratings(&self) -> &'static [&'static str] {
+        match self {
+            TableCalculator::Mcdm => &["Trivial", "Easy", "Standard", "Hard", "Extreme"],
+            TableCalculator::Wizards2014 => &["Easy", "Medium", "Hard", "Deadly"],
+            TableCalculator::Wizards2024 => &["Low", "Moderate", "High"],
+        }
+    }
+
+    /// Case-insensitive lookup of one of `ratings()`. The 2014 names are
+    /// accepted by every calculator and map onto its closest rating.
+    pub fn rating(&self, name: &str) -> Option<&'static str> {
+        let name = name.to_ascii_lowercase();
+        let alias = match (self, name.as_str()) {
+            (TableCalculator::Mcdm, "medium") => Some("Standard"),
+            (TableCalculator::Mcdm, "deadly") => Some("Extreme"),
+            (TableCalculator::Wizards2024, "easy") => Some("Low"),
+            (TableCalculator::Wizards2024, "medium") => Some("Moderate"),
+            (TableCalculator::Wizards2024, "hard" | "deadly") => Some("High"),
+            _ => None,
+        };
+        alias.or_else(|| {
+            self.ratings()
+                .iter()
+                .find(|rating| rating.eq_ignore_ascii_case(&name))
+                .copied()
+        })
+    }
+
+    pub fn rate(&self, pc_levels: &[usize], monster_crs: &[f64]) -> Result<String, EncounterError> {
+        let (pc_levels, monster_crs) = (pc_levels.to_vec(), monster_crs.to_vec());
+        match self {
+            TableCalculator::Mcdm => {
+                DifficultyCalculator::<MCDMDifficultyCalculator>::new(pc_levels, monster_crs)
+                    .calculate()
+                    .map(Into::into)
+            }
+            TableCalculator::Wizards2014 => {
+                DifficultyCalculator::<WizardDifficultyCalculator2014>::new(pc_levels, monster_crs)
+                    .calculate()
+                    .map(Into::into)
+            }
+            TableCalculator::Wizards2024 => {
+                DifficultyCalculator::<WizardDifficultyCalculator2024>::new(pc_levels, monster_crs)
+                    .calculate()
+                    .map(Into::into)
+            }
+        }
+    }
+}
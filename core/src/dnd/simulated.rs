@@ -0,0 +1,215 @@
+use crate::{
+    encounter::{Encounter, EncounterError},
+    monte_carlo::combat::{encounter_monte_carlo_iterator, CombatStats},
+};
+
+use super::{
+    generate::{generated_encounter, CHALLENGE_RATINGS},
+    DifficultyCalculatorImpl,
+};
+
+/// Combats simulated per rating by `SimulatedDifficultyCalculator`
+pub const SIMULATED_DIFFICULTY_ITERATIONS: usize = 1000;
+
+// Band thresholds. An encounter lands in the first band whose condition it
+// meets, checked from Deadly down.
+const DEADLY_WIN_RATE: f64 = 0.8;
+const DEADLY_HEROES_DOWNED: f64 = 0.5;
+const HARD_WIN_RATE: f64 = 0.95;
+const HARD_HEROES_DOWNED: f64 = 0.2;
+const HARD_HP_BURNED: f64 = 0.6;
+const MEDIUM_HEROES_DOWNED: f64 = 0.05;
+const MEDIUM_HP_BURNED: f64 = 0.3;
+const MEDIUM_RESOURCES_BURNED: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SimulatedDifficulty {
+    Easy,
+    Medium,
+    Hard,
+    Deadly,
+}
+
+impl SimulatedDifficulty {
+    pub const ALL: [SimulatedDifficulty; 4] = [
+        SimulatedDifficulty::Easy,
+        SimulatedDifficulty::Medium,
+        SimulatedDifficulty::Hard,
+        SimulatedDifficulty::Deadly,
+    ];
+}
+
+impl From<SimulatedDifficulty> for String {
+    fn from(value: SimulatedDifficulty) -> Self {
+        match value {
+            SimulatedDifficulty::Easy => "Easy".into(),
+            SimulatedDifficulty::Medium => "Medium".into(),
+            SimulatedDifficulty::Hard => "Hard".into(),
+            SimulatedDifficulty::Deadly => "Deadly".into(),
+        }
+    }
+}
+
+/// What a batch of simulated combats says about an encounter
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulatedOutcome {
+    pub win_rate: f64,
+    /// Expected number of heroes at 0 HP when the fight ends
+    pub heroes_downed: f64,
+    /// Average fraction of the party's total hit points lost
+    pub hp_burned: f64,
+    /// Average fraction of the party's limited-use resources spent
+    pub resources_burned: f64,
+    pub difficulty: SimulatedDifficulty,
+}
+
+impl SimulatedOutcome {
+    pub fn from_stats(encounter: &Encounter, stats: &CombatStats) -> Self {
+        let party_size = encounter.hero_levels().len();
+        let party_hp: usize = encounter.heroes.iter().map(|s| s.hp * s.count).sum();
+        // Built, so spell slots and feature uses count alongside listed resources
+        let heroes = encounter.build_combat().heroes;
+        let party_resources: usize = heroes.iter().map(|hero| hero.resources_max()).sum();
+
+        let win_rate = stats.hero_victories_perc;
+        let heroes_downed = party_size as f64 - stats.heroes_standing.mean();
+        let hp_burned = if party_hp > 0 {
+            1.0 - stats.party_hp_remaining.mean() / party_hp as f64
+        } else {
+            0.0
+        };
+        let resources_spent: f64 = heroes
+            .iter()
+            .filter_map(|hero| stats.resources_spent.get(&hero.name))
+            .map(|spent| spent.mean())
+            .sum();
+        let resources_burned = if party_resources > 0 {
+            resources_spent / party_resources as f64
+        } else {
+            0.0
+        };
+
+        let downed_fraction = if party_size > 0 {
+            heroes_downed / party_size as f64
+        } else {
+            0.0
+        };
+        let difficulty = if win_rate < DEADLY_WIN_RATE || downed_fraction >= DEADLY_HEROES_DOWNED {
+            SimulatedDifficulty::Deadly
+        } else if win_rate < HARD_WIN_RATE
+            || downed_fraction >= HARD_HEROES_DOWNED
+            || hp_burned >= HARD_HP_BURNED
+        {
+            SimulatedDifficulty::Hard
+        } else if downed_fraction >= MEDIUM_HEROES_DOWNED
+            || hp_burned >= MEDIUM_HP_BURNED
+            || resources_burned >= MEDIUM_RESOURCES_BURNED
+        {
+            SimulatedDifficulty::Medium
+        } else {
+            SimulatedDifficulty::Easy
+        };
+
+        SimulatedOutcome {
+            win_rate,
+            heroes_downed,
+            hp_burned,
+            resources_burned,
+            difficulty,
+        }
+    }
+}
+
+/// Simulates `encounter` `iterations` times and rates the outcome
+pub fn simulate_difficulty(encounter: &Encounter, iterations: usize) -> SimulatedOutcome {
+    let mut iterator = encounter_monte_carlo_iterator(iterations, encounter.clone());
+    for _ in iterator.by_ref() {}
+    SimulatedOutcome::from_stats(encounter, &iterator.stats)
+}
+
+/// Rates encounters by simulating them instead of reading a budget table.
+/// Only levels and CRs are available through `DifficultyCalculatorImpl`, so
+/// the fight is run between generic creatures built from those; use
+/// `simulate_difficulty` to rate a real encounter.
+pub struct SimulatedDifficultyCalculator;
+
+impl DifficultyCalculatorImpl for SimulatedDifficultyCalculator {
+    type DifficultyResult = SimulatedDifficulty;
+
+    fn calculate(
+        pc_levels: &Vec<usize>,
+        monster_crs: &Vec<f64>,
+    ) -> Result<Self::DifficultyResult, EncounterError> {
+        if let Some(level) = pc_levels.iter().find(|level| !(1..=20).contains(*level)) {
+            return Err(EncounterError::InvalidParameter(format!(
+                "party level {} isn't between 1 and 20",
+                level
+            )));
+        }
+        if let Some(cr) = monster_crs
+            .iter()
+            .find(|cr| !CHALLENGE_RATINGS.contains(cr))
+        {
+            return Err(EncounterError::InvalidParameter(format!(
+                "CR {} isn't a standard challenge rating",
+                cr
+            )));
+        }
+        let encounter = generated_encounter(pc_levels, monster_crs);
+        Ok(simulate_difficulty(&encounter, SIMULATED_DIFFICULTY_ITERATIONS).difficulty)
+    }
+}
+
+#[cfg(test)]
+mod simulated_tests {
+    use super::*;
+    use crate::{combat::level_one_encounter, dnd::DifficultyCalculator, seed_rng};
+
+    #[test]
+    fn test_outcome_bands() {
+        seed_rng(3);
+        let easy = simulate_difficulty(&level_one_encounter(1), 300);
+        assert_eq!(easy.difficulty, SimulatedDifficulty::Easy);
+        assert!(easy.win_rate > 0.99);
+
+        let deadly = simulate_difficulty(&level_one_encounter(10), 300);
+        assert_eq!(deadly.difficulty, SimulatedDifficulty::Deadly);
+        assert!(deadly.heroes_downed > easy.heroes_downed);
+        assert!(deadly.hp_burned > easy.hp_burned);
+        assert!(deadly.resources_burned >= easy.resources_burned);
+    }
+
+    #[test]
+    fn test_caster_resources_burned() {
+        seed_rng(34);
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-3-gnolls.toml"
+        );
+        let encounter = Encounter::load(path).unwrap();
+        let outcome = simulate_difficulty(&encounter, 100);
+        assert!(outcome.resources_burned > 0.0);
+        assert!(outcome.resources_burned <= 1.0);
+    }
+
+    #[test]
+    fn test_calculator() {
+        seed_rng(5);
+        let trivial = DifficultyCalculator::<SimulatedDifficultyCalculator>::new(
+            vec![5, 5, 5, 5],
+            vec![0.125],
+        );
+        assert_eq!(trivial.calculate().unwrap(), SimulatedDifficulty::Easy);
+
+        let overwhelming =
+            DifficultyCalculator::<SimulatedDifficultyCalculator>::new(vec![1, 1], vec![5.0]);
+        assert_eq!(
+            overwhelming.calculate().unwrap(),
+            SimulatedDifficulty::Deadly
+        );
+
+        let bad_cr =
+            DifficultyCalculator::<SimulatedDifficultyCalculator>::new(vec![1, 1], vec![0.3]);
+        assert!(bad_cr.calculate().is_err());
+    }
+}
@@ -1,10 +1,10 @@
-use std::usize;
-
 use phf::phf_map;
 
-use super::DifficultyCalculatorImpl;
+use super::{cr_entry, level_entry, DifficultyCalculatorImpl};
+use crate::encounter::EncounterError;
 
 static MONSTER_CR_TO_XP_2014: phf::Map<&'static str, usize> = phf_map! {
+    "0" => 10,
     "0.125" => 25,
     "0.25" => 50,
     "0.5" => 100,
@@ -178,22 +178,25 @@ pub struct WizardDifficultyCalculator2014;
 impl DifficultyCalculatorImpl for WizardDifficultyCalculator2014 {
     type DifficultyResult = WizardDifficulty2014;
 
-    fn calculate(pc_levels: &Vec<usize>, monster_crs: &Vec<f64>) -> Self::DifficultyResult {
-        let mut total_monster_xp: usize = monster_crs
+    #[allow(clippy::assign_op_pattern)]
+    fn calculate(
+        pc_levels: &Vec<usize>,
+        monster_crs: &Vec<f64>,
+    ) -> Result<Self::DifficultyResult, EncounterError> {
+        let mut total_monster_xp = monster_crs
             .iter()
-            .map(|cr| MONSTER_CR_TO_XP_2014.get(cr.to_string().as_str()).unwrap())
-            .sum();
-        let mut easy_threshold = 0;
+            .map(|cr| cr_entry(&MONSTER_CR_TO_XP_2014, *cr))
+            .sum::<Result<usize, _>>()?;
+        let mut _easy_threshold = 0;
         let mut medium_threshold = 0;
         let mut hard_threshold = 0;
         let mut deadly_threshold = 0;
 
         for i in pc_levels {
-            let i_str = i.to_string();
-            easy_threshold += EASY_XP_PER_LEVEL_2014.get(&i_str.as_str()).unwrap();
-            medium_threshold += MEDIUM_XP_PER_LEVEL_2014.get(&i_str.as_str()).unwrap();
-            hard_threshold += HARD_XP_PER_LEVEL_2014.get(&i_str.as_str()).unwrap();
-            deadly_threshold += DEADLY_XP_PER_LEVEL_2014.get(&i_str.as_str()).unwrap();
+            _easy_threshold += level_entry(&EASY_XP_PER_LEVEL_2014, *i)?;
+            medium_threshold += level_entry(&MEDIUM_XP_PER_LEVEL_2014, *i)?;
+            hard_threshold += level_entry(&HARD_XP_PER_LEVEL_2014, *i)?;
+            deadly_threshold += level_entry(&DEADLY_XP_PER_LEVEL_2014, *i)?;
         }
 
         match monster_crs.len() {
@@ -206,13 +209,13 @@ impl DifficultyCalculatorImpl for WizardDifficultyCalculator2014 {
         }
 
         if total_monster_xp >= deadly_threshold {
-            WizardDifficulty2014::Deadly
+            Ok(WizardDifficulty2014::Deadly)
         } else if total_monster_xp >= hard_threshold {
-            WizardDifficulty2014::Hard
+            Ok(WizardDifficulty2014::Hard)
         } else if total_monster_xp >= medium_threshold {
-            WizardDifficulty2014::Medium
+            Ok(WizardDifficulty2014::Medium)
         } else {
-            WizardDifficulty2014::Easy
+            Ok(WizardDifficulty2014::Easy)
         }
     }
 }
@@ -238,28 +241,31 @@ pub struct WizardDifficultyCalculator2024;
 impl DifficultyCalculatorImpl for WizardDifficultyCalculator2024 {
     type DifficultyResult = WizardDifficulty2024;
 
-    fn calculate(pc_levels: &Vec<usize>, monster_crs: &Vec<f64>) -> Self::DifficultyResult {
-        let total_monster_xp: usize = monster_crs
+    fn calculate(
+        pc_levels: &Vec<usize>,
+        monster_crs: &Vec<f64>,
+    ) -> Result<Self::DifficultyResult, EncounterError> {
+        let total_monster_xp = monster_crs
             .iter()
-            .map(|cr| MONSTER_CR_TO_XP_2014.get(cr.to_string().as_str()).unwrap())
-            .sum();
+            .map(|cr| cr_entry(&MONSTER_CR_TO_XP_2014, *cr))
+            .sum::<Result<usize, _>>()?;
         let mut low_threshold = 0;
         let mut moderate_threshold = 0;
         let mut _high_threshold = 0;
 
         for i in pc_levels {
-            let xp = XP_PER_LEVEL_2024.get(i.to_string().as_str()).unwrap();
+            let xp = level_entry(&XP_PER_LEVEL_2024, *i)?;
             low_threshold += xp.0;
             moderate_threshold += xp.1;
             _high_threshold += xp.2;
         }
 
         if total_monster_xp <= low_threshold {
-            WizardDifficulty2024::Low
+            Ok(WizardDifficulty2024::Low)
         } else if total_monster_xp <= moderate_threshold {
-            WizardDifficulty2024::Moderate
+            Ok(WizardDifficulty2024::Moderate)
         } else {
-            WizardDifficulty2024::High
+            Ok(WizardDifficulty2024::High)
         }
     }
 }
@@ -0,0 +1,923 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    battlefield::{Positioning, Square, Zone},
+    combat::{
+        Ability, Feature, Initiative, LegendaryActions, Morale, Objective, Recharge, ResourceType,
+        Rest, Spell, Spellcasting, Surprise, Trigger,
+    },
+    dnd::generate::CHALLENGE_RATINGS,
+    Action, Character, Combat, DicePool, Team,
+};
+
+#[derive(Error, Debug)]
+pub enum EncounterError {
+    #[error("couldn't read encounter file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("couldn't parse encounter file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("no creature named {0:?} in the encounter")]
+    UnknownCreature(String),
+    #[error("no spell named {0:?} in the spell list")]
+    UnknownSpell(String),
+    #[error("{0}")]
+    InvalidParameter(String),
+}
+
+/// A serializable description of a fight: who is on each side and what they
+/// can do. Encounters are the unit Monte Carlo batches are run over, since a
+/// fresh `Combat` has to be built for every simulated fight.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Encounter {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub heroes: Vec<CreatureSpec>,
+    #[serde(default)]
+    pub monsters: Vec<CreatureSpec>,
+    #[serde(default)]
+    pub waves: Vec<Wave>,
+    /// Where everyone stands; without it anyone can reach anyone
+    #[serde(default)]
+    pub positioning: Option<Positioning>,
+    #[serde(default)]
+    pub initiative: Initiative,
+    /// Which rules apply to creatures that are `surprised`
+    #[serde(default)]
+    pub surprise: Surprise,
+    /// Pairs of factions that don't fight each other
+    #[serde(default)]
+    pub alliances: Vec<(String, String)>,
+    /// What the heroes need to do to win, if not just win the fight
+    #[serde(default)]
+    pub objective: Option<Objective>,
+    /// Rounds before the fight is called a draw, 100 by default
+    #[serde(default)]
+    pub max_rounds: Option<usize>,
+    /// Rounds in a row without anyone's HP changing before the fight is
+    /// called a draw, 10 by default
+    #[serde(default)]
+    pub stalemate_rounds: Option<usize>,
+}
+
+/// Reinforcements that join the fight at the start of `round`, rolling
+/// initiative as they arrive
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Wave {
+    pub round: usize,
+    #[serde(default)]
+    pub heroes: Vec<CreatureSpec>,
+    #[serde(default)]
+    pub monsters: Vec<CreatureSpec>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CreatureSpec {
+    pub name: String,
+    pub hp: usize,
+    pub ac: usize,
+    /// Defaults to `heroes` or `monsters`, depending on the side it's listed on
+    #[serde(default)]
+    pub faction: Option<String>,
+    /// Walking speed in feet
+    #[serde(default = "default_speed")]
+    pub speed: usize,
+    #[serde(default)]
+    pub initiative_bonus: isize,
+    #[serde(default = "default_level")]
+    pub level: usize,
+    #[serde(default)]
+    pub challenge_rating: f64,
+    /// Number of identical creatures; more than one get numbered names
+    #[serde(default = "default_count")]
+    pub count: usize,
+    #[serde(default)]
+    pub actions: Vec<Action>,
+    #[serde(default)]
+    pub resources: Vec<ResourceSpec>,
+    /// e.g. "1d10+2" for a fighter with +2 CON; one per level
+    #[serde(default)]
+    pub hit_die: Option<DicePool>,
+    /// Saving throw bonuses, e.g. `saves = { dex = 2, wis = 4 }`
+    #[serde(default)]
+    pub saves: HashMap<Ability, isize>,
+    #[serde(default)]
+    pub spellcasting: Option<SpellcastingSpec>,
+    #[serde(default)]
+    pub features: Vec<Feature>,
+    #[serde(default)]
+    pub legendary_actions: Option<LegendaryActions>,
+    #[serde(default)]
+    pub lair_actions: Vec<Action>,
+    /// Regeneration and auras
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    /// Starting square on a grid; copies stand in a line to its right
+    #[serde(default)]
+    pub position: Option<Square>,
+    /// Line it starts in when the fight is in zones
+    #[serde(default)]
+    pub zone: Option<Zone>,
+    /// Caught off guard when the fight starts
+    #[serde(default)]
+    pub surprised: bool,
+    /// DEX score, for breaking initiative ties
+    #[serde(default)]
+    pub dexterity: Option<usize>,
+    #[serde(default)]
+    pub morale: Option<Morale>,
+}
+
+/// A caster's numbers plus the spells they know, by name from the spell list.
+/// Slots come from the caster type and the creature's level.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpellcastingSpec {
+    #[serde(flatten)]
+    pub spellcasting: Spellcasting,
+    #[serde(default)]
+    pub spells: Vec<String>,
+}
+
+impl SpellcastingSpec {
+    pub fn spells(&self) -> Result<Vec<Spell>, EncounterError> {
+        self.spells
+            .iter()
+            .map(|name| {
+                Spell::named(name).ok_or_else(|| EncounterError::UnknownSpell(name.clone()))
+            })
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResourceSpec {
+    pub resource: ResourceType,
+    pub max: usize,
+    #[serde(default)]
+    pub recharge: Rest,
+    /// Amount regained when it recharges; everything when omitted
+    #[serde(default)]
+    pub recharge_dice: Option<DicePool>,
+    /// Minimum d6 roll to get it back at the start of a turn, e.g. 5 for
+    /// "Recharge 5-6"
+    #[serde(default)]
+    pub recharge_roll: Option<usize>,
+}
+
+fn default_level() -> usize {
+    1
+}
+
+fn default_count() -> usize {
+    1
+}
+
+fn default_speed() -> usize {
+    30
+}
+
+impl CreatureSpec {
+    pub fn new<T: Into<String>>(name: T, hp: usize, ac: usize) -> Self {
+        CreatureSpec {
+            name: name.into(),
+            hp,
+            ac,
+            faction: None,
+            speed: default_speed(),
+            initiative_bonus: 0,
+            level: default_level(),
+            challenge_rating: 0.0,
+            count: default_count(),
+            actions: vec![],
+            resources: vec![],
+            hit_die: None,
+            saves: HashMap::new(),
+            spellcasting: None,
+            features: vec![],
+            legendary_actions: None,
+            lair_actions: vec![],
+            triggers: vec![],
+            position: None,
+            zone: None,
+            surprised: false,
+            dexterity: None,
+            morale: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), EncounterError> {
+        if !(1..=20).contains(&self.level) {
+            return Err(EncounterError::InvalidParameter(format!(
+                "{} is level {}, which isn't between 1 and 20",
+                self.name, self.level
+            )));
+        }
+        if !CHALLENGE_RATINGS.contains(&self.challenge_rating) {
+            return Err(EncounterError::InvalidParameter(format!(
+                "{} has CR {}, which isn't a standard challenge rating",
+                self.name, self.challenge_rating
+            )));
+        }
+        if let Some(spellcasting) = &self.spellcasting {
+            spellcasting.spells()?;
+        }
+        Ok(())
+    }
+
+    pub fn build(&self, team: Team) -> Vec<Character> {
+        (0..self.count)
+            .map(|i| {
+                let name = if self.count > 1 {
+                    format!("{} {}", self.name, i + 1)
+                } else {
+                    self.name.clone()
+                };
+                let mut character =
+                    Character::new(name, self.hp, self.ac, team, self.initiative_bonus)
+                        .with_level(self.level)
+                        .with_challenge_rating(self.challenge_rating)
+                        .with_actions(self.actions.clone());
+                character.speed = self.speed;
+                if let Some(faction) = &self.faction {
+                    character.faction = faction.clone();
+                }
+                character.features = self.features.clone();
+                character.lair_actions = self.lair_actions.clone();
+                character.triggers = self.triggers.clone();
+                character.position = self
+                    .position
+                    .map(|square| Square::new(square.x + i as isize, square.y));
+                character.zone = self.zone;
+                character.surprised = self.surprised;
+                character.dexterity = self.dexterity;
+                character.group = Some(self.name.clone());
+                character.morale = self.morale.clone();
+                if let Some(legendary) = &self.legendary_actions {
+                    character = character.with_legendary_actions(legendary.clone());
+                }
+                if let Some(hit_die) = &self.hit_die {
+                    character = character.with_hit_die(hit_die.clone());
+                }
+                for (ability, bonus) in &self.saves {
+                    character = character.with_save(*ability, *bonus);
+                }
+                if let Some(spec) = &self.spellcasting {
+                    character = character.with_spellcasting(spec.spellcasting.clone());
+                    // Names were checked when the encounter was loaded
+                    for spell in spec.spells().unwrap_or_default() {
+                        character.add_action(Action::CastSpell {
+                            spell,
+                            slot_level: None,
+                        });
+                    }
+                }
+                for resource in &self.resources {
+                    character.add_resource(resource.resource.clone(), resource.max);
+                    character.set_recharge(
+                        resource.resource.clone(),
+                        Recharge {
+                            on: resource.recharge,
+                            dice: resource.recharge_dice.clone(),
+                            roll: resource.recharge_roll,
+                        },
+                    );
+                }
+                character
+            })
+            .collect()
+    }
+}
+
+impl Wave {
+    pub fn validate(&self) -> Result<(), EncounterError> {
+        if self.round < 2 {
+            return Err(EncounterError::InvalidParameter(format!(
+                "a wave on round {} would be there from the start",
+                self.round
+            )));
+        }
+        self.heroes
+            .iter()
+            .chain(&self.monsters)
+            .try_for_each(CreatureSpec::validate)
+    }
+
+    pub fn schedule(&self, combat: &mut Combat) {
+        let arrivals = self
+            .heroes
+            .iter()
+            .flat_map(|spec| spec.build(Team::Heroes))
+            .chain(
+                self.monsters
+                    .iter()
+                    .flat_map(|spec| spec.build(Team::Monsters)),
+            );
+        for character in arrivals {
+            combat.schedule_arrival(self.round, character);
+        }
+    }
+}
+
+impl Encounter {
+    pub fn new(heroes: Vec<CreatureSpec>, monsters: Vec<CreatureSpec>) -> Self {
+        Encounter {
+            name: None,
+            heroes,
+            monsters,
+            waves: vec![],
+            positioning: None,
+            initiative: Initiative::default(),
+            surprise: Surprise::default(),
+            alliances: vec![],
+            objective: None,
+            max_rounds: None,
+            stalemate_rounds: None,
+        }
+    }
+
+    pub fn from_toml_str(s: &str) -> Result<Self, EncounterError> {
+        let encounter: Encounter = toml::from_str(s)?;
+        encounter.validate()?;
+        Ok(encounter)
+    }
+
+    /// Checks everything serde can't, i.e. that every named spell exists,
+    /// waves come after the first round and the objective can be met
+    pub fn validate(&self) -> Result<(), EncounterError> {
+        self.waves.iter().try_for_each(Wave::validate)?;
+        match &self.objective {
+            Some(Objective::Kill { target }) => {
+                let combat = self.build_combat();
+                if !combat.has_creature(target) {
+                    return Err(EncounterError::UnknownCreature(target.clone()));
+                }
+            }
+            Some(Objective::Escape { .. })
+                if !matches!(self.positioning, Some(Positioning::Grid { .. })) =>
+            {
+                return Err(EncounterError::InvalidParameter(
+                    "escaping needs grid positioning".into(),
+                ));
+            }
+            _ => {}
+        }
+        self.heroes
+            .iter()
+            .chain(&self.monsters)
+            .try_for_each(CreatureSpec::validate)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, EncounterError> {
+        Encounter::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn build_combat(&self) -> Combat {
+        let heroes = self
+            .heroes
+            .iter()
+            .flat_map(|spec| spec.build(Team::Heroes))
+            .collect();
+        self.build_combat_with(heroes)
+    }
+
+    /// Sets the fight up with `heroes` who have already been built, e.g. a
+    /// party carrying its wounds over from an earlier fight
+    pub fn build_combat_with(&self, heroes: Vec<Character>) -> Combat {
+        let monsters = self
+            .monsters
+            .iter()
+            .flat_map(|spec| spec.build(Team::Monsters))
+            .collect();
+        let mut combat = Combat::new(heroes, monsters)
+            .with_initiative(self.initiative)
+            .with_surprise(self.surprise);
+        for (a, b) in &self.alliances {
+            combat = combat.with_alliance(a, b);
+        }
+        if let Some(objective) = &self.objective {
+            combat = combat.with_objective(objective.clone());
+        }
+        if let Some(rounds) = self.max_rounds {
+            combat = combat.with_max_rounds(rounds);
+        }
+        if let Some(rounds) = self.stalemate_rounds {
+            combat = combat.with_stalemate_rounds(rounds);
+        }
+        if let Some(positioning) = &self.positioning {
+            combat = combat.with_positioning(positioning.clone());
+        }
+        for wave in &self.waves {
+            wave.schedule(&mut combat);
+        }
+        combat
+    }
+
+    pub fn hero_levels(&self) -> Vec<usize> {
+        self.heroes
+            .iter()
+            .flat_map(|spec| vec![spec.level; spec.count])
+            .collect()
+    }
+
+    pub fn monster_crs(&self) -> Vec<f64> {
+        self.monsters
+            .iter()
+            .flat_map(|spec| vec![spec.challenge_rating; spec.count])
+            .collect()
+    }
+
+    pub fn monster_mut(&mut self, name: &str) -> Result<&mut CreatureSpec, EncounterError> {
+        self.monsters
+            .iter_mut()
+            .find(|spec| spec.name == name)
+            .ok_or_else(|| EncounterError::UnknownCreature(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod encounter_tests {
+    use super::*;
+    use crate::{
+        battlefield::{Cover, Hazard, HazardTiming, Range, Terrain},
+        combat::DamageType,
+    };
+
+    const KOBOLDS: &str = r#"
+        name = "Kobold ambush"
+
+        [[heroes]]
+        name = "Fighter"
+        hp = 12
+        ac = 16
+        initiative_bonus = 1
+        level = 2
+        resources = [{ resource = { feature = "Second Wind" }, max = 1 }]
+
+        [[heroes.actions]]
+        type = "attack"
+        name = "Greatsword"
+        hit_bonus = 5
+        damage = "2d6+3"
+
+        [[heroes.actions]]
+        type = "second_wind"
+        healing = "1d10+1"
+        required_resources = [[{ feature = "Second Wind" }, 1]]
+
+        [[monsters]]
+        name = "Kobold"
+        hp = 5
+        ac = 12
+        challenge_rating = 0.125
+        count = 3
+        actions = [{ type = "attack", name = "Dagger", hit_bonus = 4, damage = "1d4+2" }]
+    "#;
+
+    #[test]
+    fn test_parse_encounter() {
+        let encounter = Encounter::from_toml_str(KOBOLDS).unwrap();
+        assert_eq!(encounter.name.as_deref(), Some("Kobold ambush"));
+        assert_eq!(encounter.hero_levels(), vec![2]);
+        assert_eq!(encounter.monster_crs(), vec![0.125; 3]);
+
+        let combat = encounter.build_combat();
+        assert_eq!(combat.hero_levels(), vec![2]);
+        assert_eq!(combat.monster_crs(), vec![0.125; 3]);
+        assert_eq!(combat.monsters[2].name, "Kobold 3");
+
+        let fighter = &combat.heroes[0];
+        assert_eq!(fighter.name, "Fighter");
+        assert!(fighter.has_resource(&ResourceType::Feature("Second Wind".into()), 1));
+        assert_eq!(
+            fighter.actions[0],
+            Action::Attack {
+                name: "Greatsword".into(),
+                hit_bonus: 5,
+                damage: "2d6+3".into(),
+                damage_type: None,
+                range: Range::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_example_matches_built_in() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-1-kobolds.toml"
+        );
+        let mut encounter = Encounter::load(path).unwrap();
+        encounter.name = None;
+        assert_eq!(encounter, crate::combat::level_one_encounter(6));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let encounter = Encounter::from_toml_str(KOBOLDS).unwrap();
+        let serialized = toml::to_string(&encounter).unwrap();
+        assert_eq!(Encounter::from_toml_str(&serialized).unwrap(), encounter);
+    }
+
+    #[test]
+    fn test_bad_dice() {
+        let result = Encounter::from_toml_str(
+            r#"
+            [[monsters]]
+            name = "Kobold"
+            hp = 5
+            ac = 12
+            actions = [{ type = "attack", name = "Dagger", hit_bonus = 4, damage = "1dx" }]
+            "#,
+        );
+        assert!(matches!(result, Err(EncounterError::Parse(_))));
+    }
+
+    #[test]
+    fn test_bad_levels_and_crs() {
+        let result = Encounter::from_toml_str(
+            r#"
+            [[monsters]]
+            name = "Kobold"
+            hp = 5
+            ac = 12
+            challenge_rating = 0.3
+            "#,
+        );
+        assert!(matches!(result, Err(EncounterError::InvalidParameter(_))));
+
+        let result = Encounter::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Fighter"
+            hp = 12
+            ac = 16
+            level = 0
+            "#,
+        );
+        assert!(matches!(result, Err(EncounterError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_features() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-5-ogres.toml"
+        );
+        let combat = Encounter::load(path).unwrap().build_combat();
+        let rogue = combat
+            .heroes
+            .iter()
+            .find(|hero| hero.name == "Rogue")
+            .unwrap();
+        assert_eq!(
+            rogue.features,
+            vec![
+                Feature::SneakAttack { dice: "3d6".into() },
+                Feature::CunningAction
+            ]
+        );
+        let barbarian = combat
+            .heroes
+            .iter()
+            .find(|hero| hero.name == "Barbarian")
+            .unwrap();
+        assert!(barbarian.has_resource(&ResourceType::Feature(Feature::RAGE.into()), 3));
+    }
+
+    #[test]
+    fn test_legendary_monsters() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-5-dragon.toml"
+        );
+        let combat = Encounter::load(path).unwrap().build_combat();
+        let dragon = &combat.monsters[0];
+        let legendary = dragon.legendary_actions.as_ref().unwrap();
+        assert_eq!(legendary.per_round, 2);
+        assert_eq!(legendary.options[0].cost, 1);
+        assert_eq!(legendary.options[1].cost, 2);
+        assert_eq!(dragon.lair_actions.len(), 1);
+        assert!(dragon.actions.iter().any(|action| matches!(
+            action,
+            Action::Save { name, targets: 2, .. } if name == "Acid Breath"
+        )));
+    }
+
+    #[test]
+    fn test_triggers() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-5-troll.toml"
+        );
+        let combat = Encounter::load(path).unwrap().build_combat();
+        let troll = &combat.monsters[0];
+        assert_eq!(
+            troll.triggers,
+            vec![Trigger::Regeneration {
+                healing: "10".into(),
+                suppressed_by: vec![DamageType::Acid, DamageType::Fire],
+            }]
+        );
+        assert!(matches!(
+            &troll.actions[0],
+            Action::Attack {
+                damage_type: Some(DamageType::Slashing),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_waves() {
+        let mut encounter = Encounter::from_toml_str(&format!(
+            r#"{}
+            [[waves]]
+            round = 3
+
+            [[waves.monsters]]
+            name = "Kobold"
+            hp = 5
+            ac = 12
+            count = 2
+            "#,
+            KOBOLDS
+        ))
+        .unwrap();
+        assert_eq!(encounter.waves[0].round, 3);
+        let mut combat = encounter.build_combat();
+        combat.roll_initiative();
+        let kobolds = combat.monster_crs().len();
+        combat.execute_round();
+        combat.execute_round();
+        combat.execute_round();
+        assert_eq!(combat.monster_crs().len(), kobolds + 2);
+
+        encounter.waves[0].round = 1;
+        assert!(matches!(
+            encounter.validate(),
+            Err(EncounterError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_grid_positioning() {
+        let encounter = Encounter::from_toml_str(&format!(
+            r#"positioning = {{ type = "grid", distance = 40, terrain = [
+                {{ squares = [{{ x = 2, y = 4 }}, {{ x = 3, y = 4 }}], cover = "half" }},
+                {{ name = "Brazier", squares = [{{ x = 6, y = 4 }}], hazard = {{ damage = "1d10", damage_type = "fire", when = "turn_start" }} }},
+                {{ squares = [{{ x = 0, y = 6 }}], difficult = true, chokepoint = 2 }},
+            ] }}
+            {}
+            [[monsters]]
+            name = "Kobold Slinger"
+            hp = 5
+            ac = 12
+            count = 2
+            position = {{ x = 4, y = 10 }}
+
+            [[monsters.actions]]
+            type = "attack"
+            name = "Sling"
+            hit_bonus = 4
+            damage = "1d4+2"
+            range = {{ type = "ranged", normal = 30, long = 120 }}
+            "#,
+            KOBOLDS
+        ))
+        .unwrap();
+        assert_eq!(
+            encounter.positioning,
+            Some(Positioning::Grid {
+                distance: 40,
+                terrain: vec![
+                    Terrain {
+                        cover: Cover::Half,
+                        ..Terrain::new(vec![Square::new(2, 4), Square::new(3, 4)])
+                    },
+                    Terrain {
+                        name: Some("Brazier".into()),
+                        hazard: Some(Hazard {
+                            damage: "1d10".into(),
+                            damage_type: Some(DamageType::Fire),
+                            when: HazardTiming::TurnStart,
+                        }),
+                        ..Terrain::new(vec![Square::new(6, 4)])
+                    },
+                    Terrain {
+                        difficult: true,
+                        chokepoint: Some(2),
+                        ..Terrain::new(vec![Square::new(0, 6)])
+                    },
+                ]
+            })
+        );
+        let combat = encounter.build_combat();
+        assert_eq!(combat.heroes[0].position, Some(Square::new(0, 0)));
+        let positions: Vec<_> = combat.monsters.iter().map(|m| m.position).collect();
+        assert_eq!(
+            positions,
+            [(0, 8), (1, 8), (2, 8), (4, 10), (5, 10)].map(|(x, y)| Some(Square::new(x, y)))
+        );
+        assert_eq!(
+            combat.monsters[3].actions[0].range(),
+            Some(Range::Ranged {
+                normal: 30,
+                long: 120
+            })
+        );
+    }
+
+    #[test]
+    fn test_zones() {
+        let encounter = Encounter::from_toml_str(&format!(
+            r#"positioning = {{ type = "zones" }}
+            {}
+            [[monsters]]
+            name = "Kobold Slinger"
+            hp = 5
+            ac = 12
+            actions = [{{ type = "attack", name = "Sling", hit_bonus = 4, damage = "1d4+2", range = {{ type = "ranged", normal = 30, long = 120 }} }}]
+
+            [[monsters]]
+            name = "Kobold Chief"
+            hp = 12
+            ac = 14
+            zone = "back"
+            actions = [{{ type = "attack", name = "Spear", hit_bonus = 4, damage = "1d6+2" }}]
+            "#,
+            KOBOLDS
+        ))
+        .unwrap();
+        assert_eq!(encounter.positioning, Some(Positioning::Zones));
+        let combat = encounter.build_combat();
+        assert_eq!(combat.heroes[0].zone, Some(Zone::Front));
+        let zones: Vec<_> = combat.monsters.iter().map(|m| m.zone).collect();
+        assert_eq!(
+            zones,
+            [
+                Zone::Front,
+                Zone::Front,
+                Zone::Front,
+                Zone::Ranged,
+                Zone::Back
+            ]
+            .map(Some)
+        );
+    }
+
+    #[test]
+    fn test_surprise() {
+        let encounter = Encounter::from_toml_str(&format!(
+            r#"initiative = "side"
+            surprise = "disadvantage"
+            {}
+            [[monsters]]
+            name = "Kobold Sentry"
+            hp = 5
+            ac = 12
+            dexterity = 15
+            surprised = true
+            "#,
+            KOBOLDS
+        ))
+        .unwrap();
+        assert_eq!(encounter.initiative, Initiative::Side);
+        assert_eq!(encounter.surprise, Surprise::Disadvantage);
+        let combat = encounter.build_combat();
+        let sentry = combat.monsters.last().unwrap();
+        assert!(sentry.surprised);
+        assert_eq!(sentry.dexterity_score(), 15);
+        assert!(!combat.heroes[0].surprised);
+        assert_eq!(combat.monsters[0].group.as_deref(), Some("Kobold"));
+    }
+
+    #[test]
+    fn test_factions() {
+        let encounter = Encounter::from_toml_str(&format!(
+            r#"alliances = [["heroes", "villagers"]]
+            {}
+            [[monsters]]
+            name = "Villager"
+            hp = 4
+            ac = 10
+            faction = "villagers"
+            "#,
+            KOBOLDS
+        ))
+        .unwrap();
+        assert_eq!(
+            encounter.alliances,
+            vec![("heroes".to_string(), "villagers".to_string())]
+        );
+        let combat = encounter.build_combat();
+        assert_eq!(combat.heroes[0].faction, "heroes");
+        assert_eq!(combat.monsters[0].faction, "monsters");
+        assert_eq!(combat.monsters.last().unwrap().faction, "villagers");
+    }
+
+    #[test]
+    fn test_round_limits() {
+        let encounter = Encounter::from_toml_str(
+            r#"
+            max_rounds = 3
+            stalemate_rounds = 2
+
+            [[heroes]]
+            name = "Fighter"
+            hp = 1000
+            ac = 10
+            actions = [{ type = "attack", name = "Club", hit_bonus = 20, damage = "1" }]
+
+            [[monsters]]
+            name = "Ogre"
+            hp = 1000
+            ac = 10
+            "#,
+        )
+        .unwrap();
+        assert_eq!(encounter.max_rounds, Some(3));
+
+        let mut combat = encounter.build_combat();
+        combat.run();
+        assert_eq!(combat.round, 4);
+        assert!(combat.is_draw());
+    }
+
+    #[test]
+    fn test_morale_and_objectives() {
+        let encounter = Encounter::from_toml_str(&format!(
+            r#"objective = {{ type = "kill", target = "Kobold Chief" }}
+            {}
+            [[monsters]]
+            name = "Kobold Chief"
+            hp = 12
+            ac = 14
+            morale = {{ dc = 12, surrender = true }}
+            "#,
+            KOBOLDS.replace("ac = 12", "ac = 12\nmorale = { leader = \"Kobold Chief\" }")
+        ))
+        .unwrap();
+        assert_eq!(
+            encounter.objective,
+            Some(Objective::Kill {
+                target: "Kobold Chief".into()
+            })
+        );
+        let combat = encounter.build_combat();
+        assert_eq!(
+            combat.monsters[0].morale,
+            Some(Morale {
+                leader: Some("Kobold Chief".into()),
+                ..Morale::default()
+            })
+        );
+        let chief = combat.monsters.last().unwrap().morale.clone().unwrap();
+        assert_eq!((chief.dc, chief.below, chief.surrender), (12, 0.5, true));
+
+        let result = Encounter::from_toml_str(&format!(
+            "objective = {{ type = \"kill\", target = \"Dragon\" }}\n{}",
+            KOBOLDS
+        ));
+        assert!(matches!(result, Err(EncounterError::UnknownCreature(name)) if name == "Dragon"));
+        let result = Encounter::from_toml_str(&format!(
+            "objective = {{ type = \"escape\", exits = [{{ x = 0, y = -1 }}] }}\n{}",
+            KOBOLDS
+        ));
+        assert!(matches!(result, Err(EncounterError::InvalidParameter(_))));
+    }
+
+    #[test]
+    fn test_spellcasting() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-3-gnolls.toml"
+        );
+        let encounter = Encounter::load(path).unwrap();
+        let combat = encounter.build_combat();
+        let cleric = combat
+            .heroes
+            .iter()
+            .find(|hero| hero.name == "Cleric")
+            .unwrap();
+        assert!(cleric.has_resource(&ResourceType::SpellSlot(2), 2));
+        assert_eq!(cleric.saves[&Ability::Wisdom], 5);
+        assert!(cleric.actions.iter().any(|action| matches!(
+            action,
+            Action::CastSpell { spell, slot_level: None } if spell.name == "Cure Wounds"
+        )));
+
+        let result = Encounter::from_toml_str(
+            r#"
+            [[heroes]]
+            name = "Wizard"
+            hp = 8
+            ac = 12
+            spellcasting = { attack_bonus = 5, save_dc = 13, spells = ["Fireball!"] }
+            "#,
+        );
+        assert!(matches!(result, Err(EncounterError::UnknownSpell(name)) if name == "Fireball!"));
+    }
+}
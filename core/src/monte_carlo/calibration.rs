@@ -0,0 +1,173 @@
+use rand::Rng;
+
+use crate::{
+    dnd::{
+        generate::{generated_encounter, CHALLENGE_RATINGS},
+        simulated::{simulate_difficulty, SimulatedDifficulty, SimulatedOutcome},
+        TableCalculator,
+    },
+    encounter::Encounter,
+    seed_rng, with_rng,
+};
+
+/// One generated encounter with its table ratings (in `TableCalculator::ALL`
+/// order) and what simulating it actually showed
+pub struct CalibrationPoint {
+    pub encounter: Encounter,
+    pub table_ratings: Vec<String>,
+    pub simulated: SimulatedOutcome,
+}
+
+impl CalibrationPoint {
+    pub fn table_rating(&self, calculator: TableCalculator) -> &str {
+        let index = TableCalculator::ALL
+            .iter()
+            .position(|c| *c == calculator)
+            .unwrap();
+        &self.table_ratings[index]
+    }
+}
+
+/// For every rating a table calculator gave, how the simulation rated the
+/// same encounters, in `SimulatedDifficulty::ALL` order
+pub struct CrossTab {
+    pub calculator: TableCalculator,
+    pub rows: Vec<(&'static str, [usize; 4])>,
+}
+
+pub fn cross_tab(points: &[CalibrationPoint], calculator: TableCalculator) -> CrossTab {
+    let rows = calculator
+        .ratings()
+        .iter()
+        .map(|rating| {
+            let mut counts = [0; 4];
+            for point in points
+                .iter()
+                .filter(|p| p.table_rating(calculator) == *rating)
+            {
+                let band = SimulatedDifficulty::ALL
+                    .iter()
+                    .position(|d| *d == point.simulated.difficulty)
+                    .unwrap();
+                counts[band] += 1;
+            }
+            (*rating, counts)
+        })
+        .collect();
+
+    CrossTab { calculator, rows }
+}
+
+/// Generates `encounters` random encounters (a party of 3-5 heroes of one
+/// level up to `max_level` against up to two groups of monsters), rates each
+/// with every table calculator and simulates it `iterations` times.
+/// Reseeds the dice with `seed` so a report can be reproduced.
+pub fn calibration_iterator(
+    encounters: usize,
+    iterations: usize,
+    max_level: usize,
+    seed: u64,
+) -> CalibrationIterator {
+    seed_rng(seed);
+    CalibrationIterator {
+        encounters,
+        iterations,
+        max_level: max_level.clamp(1, 20),
+        results: vec![],
+    }
+}
+
+pub struct CalibrationIterator {
+    encounters: usize,
+    iterations: usize,
+    max_level: usize,
+    pub results: Vec<CalibrationPoint>,
+}
+
+impl CalibrationIterator {
+    fn generate(&self) -> (Vec<usize>, Vec<f64>) {
+        with_rng(|rng| {
+            let level = rng.gen_range(1..=self.max_level);
+            let pc_levels = vec![level; rng.gen_range(3..=5)];
+
+            // Spend a random share of a rough CR budget so the encounters
+            // spread across the difficulty bands instead of piling up at
+            // either end
+            let budget = (pc_levels.len() * level) as f64 * rng.gen_range(0.05..0.6);
+            let kinds = rng.gen_range(1..=2);
+            let mut monster_crs = vec![];
+            for _ in 0..kinds {
+                let count = rng.gen_range(1..=4);
+                let cr = CHALLENGE_RATINGS
+                    .iter()
+                    .copied()
+                    .rfind(|cr| *cr > 0.0 && *cr <= budget / (kinds * count) as f64)
+                    .unwrap_or(0.125);
+                monster_crs.extend(vec![cr; count]);
+            }
+            (pc_levels, monster_crs)
+        })
+    }
+}
+
+impl Iterator for CalibrationIterator {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.results.len() >= self.encounters {
+            return None;
+        }
+
+        let (pc_levels, monster_crs) = self.generate();
+        let encounter = generated_encounter(&pc_levels, &monster_crs);
+        let table_ratings = TableCalculator::ALL
+            .iter()
+            .map(|calculator| {
+                calculator
+                    .rate(&pc_levels, &monster_crs)
+                    .expect("generated levels and CRs come from the tables")
+            })
+            .collect();
+        let simulated = simulate_difficulty(&encounter, self.iterations);
+
+        self.results.push(CalibrationPoint {
+            encounter,
+            table_ratings,
+            simulated,
+        });
+        Some(self.results.len() - 1)
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.encounters
+    }
+}
+
+#[cfg(test)]
+mod calibration_tests {
+    use super::*;
+
+    #[test]
+    fn test_calibration() {
+        let mut iterator = calibration_iterator(10, 50, 4, 1);
+        for _ in iterator.by_ref() {}
+        assert_eq!(iterator.results.len(), 10);
+
+        for point in &iterator.results {
+            let levels = point.encounter.hero_levels();
+            assert!((3..=5).contains(&levels.len()));
+            assert!(levels.iter().all(|l| (1..=4).contains(l)));
+            assert_eq!(point.table_ratings.len(), 3);
+        }
+
+        for calculator in TableCalculator::ALL {
+            let tab = cross_tab(&iterator.results, calculator);
+            assert_eq!(tab.rows.len(), calculator.ratings().len());
+            let total: usize = tab.rows.iter().flat_map(|(_, counts)| counts).sum();
+            assert_eq!(total, 10);
+        }
+    }
+}
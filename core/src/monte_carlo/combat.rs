@@ -1,37 +1,92 @@
 use std::collections::HashMap;
 
-use crate::{combat::build_level_one_combat, Combat};
+use rand::Rng;
+
+use crate::{
+    combat::level_one_encounter, encounter::Encounter, seed_rng, with_rng, Combat, CombatantTally,
+};
+
+use super::stats::{wilson_interval, ConfidenceInterval, Distribution, Z_95};
+
+/// Convergence checks are skipped until at least this many combats have run,
+/// so a lucky streak early on can't end the simulation prematurely.
+const MIN_CONVERGENCE_ITERATIONS: usize = 100;
 
 pub struct CombatStats {
+    pub iterations: usize,
     pub hero_victories: usize,
     pub hero_victories_perc: f64,
+    pub hero_victories_ci: ConfidenceInterval,
     pub monster_victories: usize,
     pub monster_victories_perc: f64,
+    pub monster_victories_ci: ConfidenceInterval,
+    pub draws: usize, // Stopped at the round limit or in a stalemate
+    pub draws_perc: f64,
+    pub draws_ci: ConfidenceInterval,
     pub average_rounds: f64,
+    pub average_rounds_std_err: f64,
     pub hero_ko_counts: HashMap<String, usize>, // How often each hero went down
     pub monster_ko_counts: HashMap<String, usize>,
     pub decisive_victories: usize, // All enemies dead, all heroes alive
     pub decisive_victories_perc: f64,
+    pub decisive_victories_ci: ConfidenceInterval,
     pub pyrrhic_victories: usize, // Heroes win but most are down
+    pub pyrrhic_victories_perc: f64,
+    pub pyrrhic_victories_ci: ConfidenceInterval,
+    /// Fights each faction was left standing in; allies share a win
+    pub faction_victories: HashMap<String, usize>,
+    pub routs: usize, // Losers fled or surrendered rather than all going down
+    pub routs_perc: f64,
+    pub wipes: usize, // Every one of the losers went down
+    pub wipes_perc: f64,
 
-    round_counts: Vec<usize>,
+    pub rounds: Distribution,
+    pub party_hp_remaining: Distribution,
+    pub heroes_standing: Distribution,
+    pub damage_dealt: HashMap<String, Distribution>,
+    pub damage_taken: HashMap<String, Distribution>,
+    pub healing_done: HashMap<String, Distribution>,
+    pub resources_spent: HashMap<String, Distribution>,
+    pub contributions: HashMap<String, CombatantContribution>,
 }
 
 impl Default for CombatStats {
     fn default() -> Self {
         CombatStats {
+            iterations: 0,
             hero_victories: 0,
             hero_victories_perc: 0.0,
+            hero_victories_ci: ConfidenceInterval::default(),
             monster_victories: 0,
             monster_victories_perc: 0.0,
+            monster_victories_ci: ConfidenceInterval::default(),
+            draws: 0,
+            draws_perc: 0.0,
+            draws_ci: ConfidenceInterval::default(),
             average_rounds: 0.0,
+            average_rounds_std_err: 0.0,
             hero_ko_counts: HashMap::new(),
             monster_ko_counts: HashMap::new(),
             decisive_victories: 0,
             decisive_victories_perc: 0.0,
+            decisive_victories_ci: ConfidenceInterval::default(),
             pyrrhic_victories: 0,
+            pyrrhic_victories_perc: 0.0,
+            faction_victories: HashMap::new(),
+            routs: 0,
+            routs_perc: 0.0,
+            wipes: 0,
+            wipes_perc: 0.0,
+            pyrrhic_victories_ci: ConfidenceInterval::default(),
 
-            round_counts: vec![],
+            rounds: Distribution::new(),
+            party_hp_remaining: Distribution::new(),
+            heroes_standing: Distribution::new(),
+            damage_dealt: HashMap::new(),
+            damage_taken: HashMap::new(),
+            healing_done: HashMap::new(),
+            resources_spent: HashMap::new(),
+            contributions: HashMap::new(),
         }
     }
 }
@@ -41,21 +96,216 @@ impl CombatStats {
         CombatStats::default()
     }
 
-    fn finalize(&mut self) {
-        let len = self.round_counts.len() as f64;
-        let average_rounds: f64 = self
-            .round_counts
-            .clone()
-            .into_iter()
-            .map(|i| i as f64)
-            .sum();
+    /// Folds the outcome of one finished combat into the stats
+    pub fn record(&mut self, combat: &Combat) {
+        let heroes_won = combat.heroes_won();
+        let heroes_standing = combat.heroes.iter().filter(|c| c.current_hp > 0).count();
+
+        if heroes_won {
+            self.hero_victories += 1;
+            if heroes_standing == combat.heroes.len() {
+                self.decisive_victories += 1;
+            }
+            if heroes_standing * 2 < combat.heroes.len() {
+                self.pyrrhic_victories += 1;
+            }
+        } else if combat.is_draw() {
+            self.draws += 1;
+        } else {
+            self.monster_victories += 1;
+        }
+        if combat.routed() {
+            self.routs += 1;
+        }
+        if combat.wiped() {
+            self.wipes += 1;
+        }
+        for faction in combat.winners() {
+            *self.faction_victories.entry(faction).or_insert(0) += 1;
+        }
+
+        for hero in &combat.heroes {
+            if hero.current_hp == 0 {
+                *self.hero_ko_counts.entry(hero.name.clone()).or_insert(0) += 1;
+            }
+        }
+
+        for monster in &combat.monsters {
+            if monster.current_hp == 0 {
+                *self
+                    .monster_ko_counts
+                    .entry(monster.name.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+
+        self.rounds.record(combat.rounds_fought());
+        self.party_hp_remaining
+            .record(combat.heroes.iter().map(|c| c.current_hp).sum());
+        self.heroes_standing.record(heroes_standing);
+
+        for combatant in combat.heroes.iter().chain(combat.monsters.iter()) {
+            let tally = combat.tally_of(combatant);
+            let name = &combatant.name;
+            record_for(&mut self.damage_dealt, name, tally.damage_dealt);
+            record_for(&mut self.damage_taken, name, tally.damage_taken);
+            record_for(&mut self.healing_done, name, tally.healing_done);
+            record_for(&mut self.resources_spent, name, tally.resources_spent);
+
+            let rounds_fought = combat.rounds_fought();
+            let rounds_survived = match tally.downed_in_round {
+                Some(round) if combatant.current_hp == 0 => round - 1,
+                _ => rounds_fought,
+            };
+            self.contributions
+                .entry(name.clone())
+                .or_default()
+                .record(&tally, rounds_survived);
+        }
+    }
+
+    /// The widest 95% interval across every reported outcome rate. Used as
+    /// the convergence criterion when simulating to a target precision.
+    pub fn max_interval_width(&self) -> f64 {
+        let trials = self.rounds.len();
+        [
+            self.hero_victories,
+            self.monster_victories,
+            self.draws,
+            self.decisive_victories,
+            self.pyrrhic_victories,
+            self.routs,
+            self.wipes,
+        ]
+        .iter()
+        .map(|successes| wilson_interval(*successes, trials, Z_95).width())
+        .fold(0.0, f64::max)
+    }
+
+    pub(crate) fn finalize(&mut self) {
+        let trials = self.rounds.len();
+        let len = trials as f64;
 
-        self.average_rounds = average_rounds / len;
+        self.iterations = trials;
+        self.average_rounds = self.rounds.mean();
+        self.average_rounds_std_err = self.rounds.standard_error();
 
         self.hero_victories_perc = self.hero_victories as f64 / len;
         self.monster_victories_perc = self.monster_victories as f64 / len;
+        self.draws_perc = self.draws as f64 / len;
         self.decisive_victories_perc = self.decisive_victories as f64 / len;
+        self.pyrrhic_victories_perc = self.pyrrhic_victories as f64 / len;
+        self.routs_perc = self.routs as f64 / len;
+        self.wipes_perc = self.wipes as f64 / len;
+
+        self.hero_victories_ci = wilson_interval(self.hero_victories, trials, Z_95);
+        self.monster_victories_ci = wilson_interval(self.monster_victories, trials, Z_95);
+        self.draws_ci = wilson_interval(self.draws, trials, Z_95);
+        self.decisive_victories_ci = wilson_interval(self.decisive_victories, trials, Z_95);
+        self.pyrrhic_victories_ci = wilson_interval(self.pyrrhic_victories, trials, Z_95);
+
+        self.rounds.finalize();
+        self.party_hp_remaining.finalize();
+        self.heroes_standing.finalize();
+        for distributions in [
+            &mut self.damage_dealt,
+            &mut self.damage_taken,
+            &mut self.healing_done,
+            &mut self.resources_spent,
+        ] {
+            distributions.values_mut().for_each(Distribution::finalize);
+        }
+    }
+}
+
+/// Who carried the fight: per-combatant totals summed across every simulated
+/// combat, with helpers for the per-combat averages and rates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CombatantContribution {
+    pub combats: usize,
+    pub attacks: usize,
+    pub hits: usize,
+    pub crits: usize,
+    pub kills: usize,
+    pub damage_dealt: usize,
+    pub damage_taken: usize,
+    pub healing_given: usize,
+    pub healing_received: usize,
+    pub turns_incapacitated: usize,
+    pub concentration_broken: usize,
+    pub rounds_survived: usize,
+}
+
+impl CombatantContribution {
+    fn record(&mut self, tally: &CombatantTally, rounds_survived: usize) {
+        self.combats += 1;
+        self.attacks += tally.attacks;
+        self.hits += tally.hits;
+        self.crits += tally.crits;
+        self.kills += tally.kills;
+        self.damage_dealt += tally.damage_dealt;
+        self.damage_taken += tally.damage_taken;
+        self.healing_given += tally.healing_done;
+        self.healing_received += tally.healing_received;
+        self.turns_incapacitated += tally.turns_incapacitated;
+        self.concentration_broken += tally.concentration_broken;
+        self.rounds_survived += rounds_survived;
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        ratio(self.hits, self.attacks)
+    }
+
+    pub fn crit_rate(&self) -> f64 {
+        ratio(self.crits, self.attacks)
+    }
+
+    pub fn average_damage_dealt(&self) -> f64 {
+        ratio(self.damage_dealt, self.combats)
+    }
+
+    pub fn average_damage_taken(&self) -> f64 {
+        ratio(self.damage_taken, self.combats)
     }
+
+    pub fn average_kills(&self) -> f64 {
+        ratio(self.kills, self.combats)
+    }
+
+    pub fn average_healing_given(&self) -> f64 {
+        ratio(self.healing_given, self.combats)
+    }
+
+    pub fn average_healing_received(&self) -> f64 {
+        ratio(self.healing_received, self.combats)
+    }
+
+    pub fn average_turns_incapacitated(&self) -> f64 {
+        ratio(self.turns_incapacitated, self.combats)
+    }
+
+    pub fn average_concentration_broken(&self) -> f64 {
+        ratio(self.concentration_broken, self.combats)
+    }
+
+    pub fn average_rounds_survived(&self) -> f64 {
+        ratio(self.rounds_survived, self.combats)
+    }
+}
+
+fn ratio(numerator: usize, denominator: usize) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+fn record_for(distributions: &mut HashMap<String, Distribution>, name: &str, sample: usize) {
+    distributions
+        .entry(name.to_string())
+        .or_default()
+        .record(sample);
 }
 
 pub fn combat_monte_carlo_iterator(
@@ -63,21 +313,62 @@ pub fn combat_monte_carlo_iterator(
     _verbose: bool,
     num_kobolds: usize,
 ) -> CombatMonteCarloIterator {
+    encounter_monte_carlo_iterator(iterations, level_one_encounter(num_kobolds))
+}
+
+/// Keeps simulating until the 95% confidence interval of every outcome rate
+/// is narrower than `target_width` (e.g. `0.02` for +/- 1%), or
+/// `max_iterations` combats have run, whichever comes first.
+pub fn combat_monte_carlo_until_converged(
+    target_width: f64,
+    max_iterations: usize,
+    _verbose: bool,
+    num_kobolds: usize,
+) -> CombatMonteCarloIterator {
+    encounter_monte_carlo_until_converged(
+        target_width,
+        max_iterations,
+        level_one_encounter(num_kobolds),
+    )
+}
+
+pub fn encounter_monte_carlo_iterator(
+    iterations: usize,
+    encounter: Encounter,
+) -> CombatMonteCarloIterator {
+    // Kept small enough to write into a recording
+    let seed = with_rng(|rng| rng.gen::<u32>()) as u64;
     let collection = CombatMonteCarloCollection {
         iterations,
-        num_kobolds,
+        encounter,
+        target_width: None,
+        seed,
     };
 
     CombatMonteCarloIterator {
         collection,
         index: 0,
         stats: CombatStats::default(),
+        extremes: Extremes::default(),
     }
 }
 
+pub fn encounter_monte_carlo_until_converged(
+    target_width: f64,
+    max_iterations: usize,
+    encounter: Encounter,
+) -> CombatMonteCarloIterator {
+    let mut iterator = encounter_monte_carlo_iterator(max_iterations, encounter);
+    iterator.collection.target_width = Some(target_width);
+    iterator
+}
+
 pub struct CombatMonteCarloCollection {
     iterations: usize,
-    num_kobolds: usize,
+    encounter: Encounter,
+    target_width: Option<f64>,
+    /// Combat N is rolled from seed + N
+    seed: u64,
 }
 
 pub struct CombatMonteCarloIterator {
@@ -85,63 +376,100 @@ pub struct CombatMonteCarloIterator {
     index: usize,
 
     pub stats: CombatStats,
+    pub extremes: Extremes,
 }
 
-impl CombatMonteCarloIterator {
-    fn update_stats(&mut self, combat: &Combat) {
-        if combat.heroes_won() {
-            self.stats.hero_victories += 1;
-        } else {
-            self.stats.monster_victories += 1;
-        }
+/// A run from a batch worth a closer look, with the seed to play it again
+/// from
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NotableRun {
+    pub seed: u64,
+    pub rounds: usize,
+}
 
-        for hero in &combat.heroes {
-            if hero.current_hp == 0 {
-                let stat = self
-                    .stats
-                    .hero_ko_counts
-                    .entry(hero.name.clone())
-                    .or_insert(0);
-                *stat += 1;
-            }
+/// The most extreme runs of a batch, up to `keep` of each kind
+#[derive(Clone, Debug, Default)]
+pub struct Extremes {
+    keep: usize,
+    /// Quickest fights the heroes lost with all of them down, quickest first
+    pub fastest_wipes: Vec<NotableRun>,
+    /// Longest first
+    pub longest_fights: Vec<NotableRun>,
+}
+
+impl Extremes {
+    pub fn new(keep: usize) -> Self {
+        Extremes {
+            keep,
+            ..Extremes::default()
         }
+    }
 
-        for monster in &combat.monsters {
-            if monster.current_hp == 0 {
-                let stat = self
-                    .stats
-                    .monster_ko_counts
-                    .entry(monster.name.clone())
-                    .or_insert(0);
-                *stat += 1;
-            }
+    fn record(&mut self, seed: u64, combat: &Combat) {
+        if self.keep == 0 {
+            return;
         }
+        let run = NotableRun {
+            seed,
+            rounds: combat.rounds_fought(),
+        };
+        if !combat.heroes_won() && combat.heroes.iter().all(|hero| hero.current_hp == 0) {
+            self.fastest_wipes.push(run);
+            // Stable, so the first of several equally quick runs stays ahead
+            self.fastest_wipes.sort_by_key(|run| run.rounds);
+            self.fastest_wipes.truncate(self.keep);
+        }
+        self.longest_fights.push(run);
+        self.longest_fights
+            .sort_by_key(|run| std::cmp::Reverse(run.rounds));
+        self.longest_fights.truncate(self.keep);
+    }
+}
 
-        self.stats.round_counts.push(combat.round);
+impl CombatMonteCarloIterator {
+    /// Rolls combat N from `seed + N`, so any of them can be played again
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.collection.seed = seed;
+        self
+    }
 
-        if combat.heroes.iter().all(|c| c.current_hp > 0) {
-            self.stats.decisive_victories += 1;
-        }
+    /// Keeps the seeds of the `keep` quickest wipes and longest fights
+    pub fn with_extremes(mut self, keep: usize) -> Self {
+        self.extremes = Extremes::new(keep);
+        self
+    }
+
+    fn update_stats(&mut self, combat: &Combat) {
+        self.stats.record(combat);
     }
 
     fn finalize_stats(&mut self) {
         self.stats.finalize();
     }
+
+    fn converged(&self) -> bool {
+        match self.collection.target_width {
+            Some(target_width) => {
+                self.index >= MIN_CONVERGENCE_ITERATIONS
+                    && self.stats.max_interval_width() <= target_width
+            }
+            None => false,
+        }
+    }
 }
 
 impl Iterator for CombatMonteCarloIterator {
     type Item = usize;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.index < self.collection.iterations {
-            let mut combat = build_level_one_combat(self.collection.num_kobolds);
-            combat.roll_initiative();
-
-            while combat.is_ongoing() {
-                combat.execute_round();
-            }
+        if self.index < self.collection.iterations && !self.converged() {
+            let seed = self.collection.seed.wrapping_add(self.index as u64);
+            seed_rng(seed);
+            let mut combat = self.collection.encounter.build_combat();
+            combat.run();
 
             self.update_stats(&combat);
+            self.extremes.record(seed, &combat);
             let ret = Some(self.index);
             self.index += 1;
 
@@ -159,3 +487,124 @@ impl Iterator for CombatMonteCarloIterator {
         self.collection.iterations
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{recording::Recording, Character, Team};
+
+    #[test]
+    fn test_fixed_iterations() {
+        let mut iterator = combat_monte_carlo_iterator(50, false, 2);
+        for _ in iterator.by_ref() {}
+
+        let stats = iterator.stats;
+        assert_eq!(stats.iterations, 50);
+        assert_eq!(stats.hero_victories + stats.monster_victories, 50);
+        assert_eq!(stats.draws, 0);
+        assert_eq!(
+            stats.faction_victories.get("heroes").copied().unwrap_or(0),
+            stats.hero_victories
+        );
+        // Kobolds fight to the end
+        assert_eq!(stats.routs, 0);
+        assert_eq!(stats.wipes, 50);
+        assert!(stats.hero_victories_ci.contains(stats.hero_victories_perc));
+        assert!(stats.average_rounds_std_err >= 0.0);
+        assert!(stats.decisive_victories <= stats.hero_victories);
+        assert!(stats.pyrrhic_victories <= stats.hero_victories - stats.decisive_victories);
+
+        assert_eq!(stats.rounds.len(), 50);
+        assert_eq!(stats.heroes_standing.len(), 50);
+        assert!(stats.heroes_standing.max() <= 3);
+        assert_eq!(stats.party_hp_remaining.len(), 50);
+        for name in ["Fighter", "Cleric", "Rogue", "Kobold 1", "Kobold 2"] {
+            assert_eq!(stats.damage_dealt[name].len(), 50);
+            assert_eq!(stats.damage_taken[name].len(), 50);
+        }
+        // Second Wind can only be used once per combat
+        assert!(stats.resources_spent["Fighter"].max() <= 1);
+        assert_eq!(stats.healing_done["Rogue"].max(), 0);
+
+        let fighter = &stats.contributions["Fighter"];
+        assert_eq!(fighter.combats, 50);
+        assert!(fighter.attacks > 0);
+        assert!(fighter.hits <= fighter.attacks);
+        assert!(fighter.crits <= fighter.hits);
+        assert!(fighter.hit_rate() > 0.0 && fighter.hit_rate() <= 1.0);
+        let rounds: usize = stats.rounds.samples().iter().sum();
+        assert!(fighter.rounds_survived <= rounds);
+        let kills: usize = ["Fighter", "Cleric", "Rogue"]
+            .iter()
+            .map(|name| stats.contributions[*name].kills)
+            .sum();
+        let kobold_kos: usize = stats.monster_ko_counts.values().sum();
+        assert_eq!(kills, kobold_kos);
+    }
+
+    #[test]
+    fn test_rounds_fought() {
+        // Nobody can hurt anybody, so it's a draw after two quiet rounds
+        let mut combat = Combat::new(
+            vec![Character::new("Fighter", 10, 15, Team::Heroes, 0)],
+            vec![Character::new("Kobold", 5, 12, Team::Monsters, 0)],
+        )
+        .with_stalemate_rounds(2);
+        combat.run();
+        assert_eq!(combat.rounds_fought(), 2);
+
+        let mut stats = CombatStats::new();
+        stats.record(&combat);
+        stats.finalize();
+        assert_eq!(stats.rounds.samples(), &vec![2]);
+        assert_eq!(stats.average_rounds, 2.0);
+        assert_eq!(
+            stats.contributions["Fighter"].average_rounds_survived(),
+            2.0
+        );
+    }
+
+    #[test]
+    fn test_extremes() {
+        let mut iterator = combat_monte_carlo_iterator(200, false, 8)
+            .with_seed(5)
+            .with_extremes(3);
+        for _ in iterator.by_ref() {}
+
+        let extremes = &iterator.extremes;
+        assert_eq!(extremes.longest_fights.len(), 3);
+        assert!(!extremes.fastest_wipes.is_empty() && extremes.fastest_wipes.len() <= 3);
+        assert!(extremes
+            .longest_fights
+            .windows(2)
+            .all(|pair| pair[0].rounds >= pair[1].rounds));
+        assert!(extremes
+            .fastest_wipes
+            .windows(2)
+            .all(|pair| pair[0].rounds <= pair[1].rounds));
+        let seeds = 5..205;
+        assert!(extremes
+            .longest_fights
+            .iter()
+            .all(|run| seeds.contains(&run.seed)));
+
+        // Each can be played again from its seed
+        let longest = extremes.longest_fights[0];
+        let (_, combat) = Recording::record(&level_one_encounter(8), longest.seed);
+        assert_eq!(combat.rounds_fought(), longest.rounds);
+    }
+
+    #[test]
+    fn test_stops_when_converged() {
+        let mut iterator = combat_monte_carlo_until_converged(0.25, 100_000, false, 2);
+        for _ in iterator.by_ref() {}
+
+        let stats = iterator.stats;
+        assert!(stats.iterations >= MIN_CONVERGENCE_ITERATIONS);
+        assert!(stats.iterations < 100_000);
+        assert!(stats.hero_victories_ci.width() <= 0.25);
+        assert!(stats.decisive_victories_ci.width() <= 0.25);
+        assert!(stats.monster_victories_ci.width() <= 0.25);
+        assert!(stats.pyrrhic_victories_ci.width() <= 0.25);
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use crate::{encounter::Encounter, seed_rng, Combat};
+
+use super::{combat::CombatStats, stats::PairedDifference};
+
+/// Head-to-head results of two encounters simulated with common random
+/// numbers. Differences are always A - B.
+pub struct Comparison {
+    pub a: CombatStats,
+    pub b: CombatStats,
+    pub hero_victories: PairedDifference,
+    pub decisive_victories: PairedDifference,
+    pub draws: PairedDifference,
+    pub routs: PairedDifference,
+    pub wipes: PairedDifference,
+    pub rounds: PairedDifference,
+    /// K/O rate for every hero that appears (by name) in both encounters
+    pub hero_ko_rates: HashMap<String, PairedDifference>,
+}
+
+#[derive(Default)]
+struct Outcomes {
+    hero_victories: Vec<f64>,
+    decisive_victories: Vec<f64>,
+    draws: Vec<f64>,
+    routs: Vec<f64>,
+    wipes: Vec<f64>,
+    rounds: Vec<f64>,
+    hero_kos: HashMap<String, Vec<f64>>,
+}
+
+impl Outcomes {
+    fn record(&mut self, combat: &Combat) {
+        let heroes_won = combat.heroes_won();
+        let all_standing = combat.heroes.iter().all(|c| c.current_hp > 0);
+        self.hero_victories.push(indicator(heroes_won));
+        self.decisive_victories
+            .push(indicator(heroes_won && all_standing));
+        self.draws.push(indicator(combat.is_draw()));
+        self.routs.push(indicator(combat.routed()));
+        self.wipes.push(indicator(combat.wiped()));
+        self.rounds.push(combat.rounds_fought() as f64);
+        for hero in &combat.heroes {
+            self.hero_kos
+                .entry(hero.name.clone())
+                .or_default()
+                .push(indicator(hero.current_hp == 0));
+        }
+    }
+}
+
+fn indicator(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// Simulates `a` and `b` side by side. Combat `i` of each encounter starts
+/// from the same seed (`seed + i`), so both see the same dice for as long as
+/// they make the same rolls and the comparison isn't drowned out by luck.
+pub fn compare_iterator(
+    a: Encounter,
+    b: Encounter,
+    iterations: usize,
+    seed: u64,
+) -> CompareIterator {
+    CompareIterator {
+        a,
+        b,
+        iterations,
+        seed,
+        index: 0,
+        a_outcomes: Outcomes::default(),
+        b_outcomes: Outcomes::default(),
+        a_stats: CombatStats::new(),
+        b_stats: CombatStats::new(),
+        comparison: None,
+    }
+}
+
+pub struct CompareIterator {
+    a: Encounter,
+    b: Encounter,
+    iterations: usize,
+    seed: u64,
+    index: usize,
+
+    a_outcomes: Outcomes,
+    b_outcomes: Outcomes,
+    a_stats: CombatStats,
+    b_stats: CombatStats,
+
+    pub comparison: Option<Comparison>,
+}
+
+impl CompareIterator {
+    fn run(&self, encounter: &Encounter) -> Combat {
+        seed_rng(self.seed.wrapping_add(self.index as u64));
+        let mut combat = encounter.build_combat();
+        combat.run();
+        combat
+    }
+
+    fn finalize(&mut self) {
+        let mut a_stats = std::mem::take(&mut self.a_stats);
+        let mut b_stats = std::mem::take(&mut self.b_stats);
+        a_stats.finalize();
+        b_stats.finalize();
+
+        let a = &self.a_outcomes;
+        let b = &self.b_outcomes;
+        let hero_ko_rates = a
+            .hero_kos
+            .iter()
+            .filter_map(|(name, a_kos)| {
+                b.hero_kos
+                    .get(name)
+                    .map(|b_kos| (name.clone(), PairedDifference::from_pairs(a_kos, b_kos)))
+            })
+            .collect();
+
+        self.comparison = Some(Comparison {
+            hero_victories: PairedDifference::from_pairs(&a.hero_victories, &b.hero_victories),
+            decisive_victories: PairedDifference::from_pairs(
+                &a.decisive_victories,
+                &b.decisive_victories,
+            ),
+            draws: PairedDifference::from_pairs(&a.draws, &b.draws),
+            routs: PairedDifference::from_pairs(&a.routs, &b.routs),
+            wipes: PairedDifference::from_pairs(&a.wipes, &b.wipes),
+            rounds: PairedDifference::from_pairs(&a.rounds, &b.rounds),
+            hero_ko_rates,
+            a: a_stats,
+            b: b_stats,
+        });
+    }
+}
+
+impl Iterator for CompareIterator {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.iterations {
+            let combat = self.run(&self.a);
+            self.a_outcomes.record(&combat);
+            self.a_stats.record(&combat);
+
+            let combat = self.run(&self.b);
+            self.b_outcomes.record(&combat);
+            self.b_stats.record(&combat);
+
+            let ret = Some(self.index);
+            self.index += 1;
+            return ret;
+        }
+
+        if self.comparison.is_none() {
+            self.finalize();
+        }
+        None
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.iterations
+    }
+}
+
+#[cfg(test)]
+mod compare_tests {
+    use super::*;
+    use crate::combat::level_one_encounter;
+
+    #[test]
+    fn test_identical_encounters_match_exactly() {
+        let encounter = level_one_encounter(4);
+        let mut iterator = compare_iterator(encounter.clone(), encounter, 100, 7);
+        for _ in iterator.by_ref() {}
+
+        let comparison = iterator.comparison.unwrap();
+        assert_eq!(comparison.a.iterations, 100);
+        assert_eq!(comparison.b.iterations, 100);
+        // Same seeds and same encounter means the same fights
+        assert_eq!(comparison.hero_victories.difference, 0.0);
+        assert_eq!(comparison.rounds.difference, 0.0);
+        // Counts rounds the same way the per-encounter stats do
+        assert!((comparison.rounds.a_mean - comparison.a.average_rounds).abs() < 1e-9);
+        assert_eq!(comparison.hero_victories.p_value, 1.0);
+        assert_eq!(comparison.hero_ko_rates.len(), 3);
+        assert!(comparison
+            .hero_ko_rates
+            .values()
+            .all(|diff| diff.difference == 0.0));
+    }
+
+    #[test]
+    fn test_harder_encounter_is_significantly_worse() {
+        let mut iterator =
+            compare_iterator(level_one_encounter(2), level_one_encounter(9), 300, 11);
+        for _ in iterator.by_ref() {}
+
+        let comparison = iterator.comparison.unwrap();
+        assert!(comparison.hero_victories.difference > 0.0);
+        assert!(comparison.hero_victories.significant());
+        assert!(comparison.hero_victories.ci.lower > 0.0);
+    }
+}
@@ -0,0 +1,173 @@
+use crate::day::{AdventuringDay, DayResult};
+
+use super::stats::{wilson_interval, ConfidenceInterval, Distribution, Z_95};
+
+/// How far into an adventuring day the party gets, over many simulated days
+pub struct DayStats {
+    pub iterations: usize,
+    pub completed_days: usize, // every encounter won
+    pub completed_days_perc: f64,
+    pub completed_days_ci: ConfidenceInterval,
+    pub encounters_won: Distribution,
+    pub encounters: Vec<DayEncounterStats>,
+}
+
+/// Outcomes of one encounter of the day, over the days that reached it
+pub struct DayEncounterStats {
+    pub name: String,
+    pub reached: usize,
+    pub won: usize,
+    pub rounds: Distribution,
+    pub party_hp_remaining: Distribution,
+    pub heroes_standing: Distribution,
+    pub resources_remaining: Distribution,
+}
+
+impl DayEncounterStats {
+    fn new(name: String) -> Self {
+        DayEncounterStats {
+            name,
+            reached: 0,
+            won: 0,
+            rounds: Distribution::new(),
+            party_hp_remaining: Distribution::new(),
+            heroes_standing: Distribution::new(),
+            resources_remaining: Distribution::new(),
+        }
+    }
+
+    /// Share of the days that reached this encounter and won it
+    pub fn win_rate(&self) -> f64 {
+        if self.reached == 0 {
+            0.0
+        } else {
+            self.won as f64 / self.reached as f64
+        }
+    }
+}
+
+impl DayStats {
+    fn new(day: &AdventuringDay) -> Self {
+        DayStats {
+            iterations: 0,
+            completed_days: 0,
+            completed_days_perc: 0.0,
+            completed_days_ci: ConfidenceInterval::default(),
+            encounters_won: Distribution::new(),
+            encounters: day
+                .encounter_names()
+                .into_iter()
+                .map(DayEncounterStats::new)
+                .collect(),
+        }
+    }
+
+    pub fn record(&mut self, result: &DayResult) {
+        let won = result.encounters_won();
+        if won == self.encounters.len() {
+            self.completed_days += 1;
+        }
+        self.encounters_won.record(won);
+
+        for (stats, encounter) in self.encounters.iter_mut().zip(&result.encounters) {
+            stats.reached += 1;
+            if encounter.heroes_won {
+                stats.won += 1;
+            }
+            stats.rounds.record(encounter.rounds);
+            stats.party_hp_remaining.record(encounter.party_hp);
+            stats.heroes_standing.record(encounter.heroes_standing);
+            stats
+                .resources_remaining
+                .record(encounter.resources_remaining);
+        }
+    }
+
+    fn finalize(&mut self) {
+        self.iterations = self.encounters_won.len();
+        self.completed_days_perc = if self.iterations == 0 {
+            0.0
+        } else {
+            self.completed_days as f64 / self.iterations as f64
+        };
+        self.completed_days_ci = wilson_interval(self.completed_days, self.iterations, Z_95);
+
+        self.encounters_won.finalize();
+        for stats in self.encounters.iter_mut() {
+            stats.rounds.finalize();
+            stats.party_hp_remaining.finalize();
+            stats.heroes_standing.finalize();
+            stats.resources_remaining.finalize();
+        }
+    }
+}
+
+pub fn day_monte_carlo_iterator(iterations: usize, day: AdventuringDay) -> DayMonteCarloIterator {
+    DayMonteCarloIterator {
+        iterations,
+        index: 0,
+        stats: DayStats::new(&day),
+        day,
+    }
+}
+
+pub struct DayMonteCarloIterator {
+    iterations: usize,
+    index: usize,
+    day: AdventuringDay,
+
+    pub stats: DayStats,
+}
+
+impl Iterator for DayMonteCarloIterator {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.iterations {
+            let result = self.day.run();
+            self.stats.record(&result);
+
+            let ret = Some(self.index);
+            self.index += 1;
+            return ret;
+        }
+
+        self.stats.finalize();
+        None
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.iterations
+    }
+}
+
+#[cfg(test)]
+mod day_tests {
+    use super::*;
+
+    #[test]
+    fn test_day_stats() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../encounters/level-1-day.toml"
+        );
+        let day = AdventuringDay::load(path).unwrap();
+        let mut iterator = day_monte_carlo_iterator(100, day);
+        for _ in iterator.by_ref() {}
+
+        let stats = iterator.stats;
+        assert_eq!(stats.iterations, 100);
+        assert!(stats.completed_days_ci.contains(stats.completed_days_perc));
+        assert_eq!(stats.encounters.len(), 4);
+        assert_eq!(stats.encounters[0].reached, 100);
+        // Every day that got past an encounter reached the next one
+        for pair in stats.encounters.windows(2) {
+            assert_eq!(pair[0].won, pair[1].reached);
+        }
+        assert_eq!(stats.encounters[3].won, stats.completed_days);
+        assert!(stats.encounters_won.max() <= 4);
+    }
+}
@@ -48,8 +48,8 @@ pub fn run_dice_monte_carlo(iterations: usize) -> Vec<DieStats> {
             .join("\n");
 
         results.push(DieStats {
-            sides: sides,
-            counts: counts,
+            sides,
+            counts,
             mean,
             expected_mean,
             chi_square,
@@ -139,7 +139,7 @@ impl Iterator for DiceMonteCarloIterator {
                     .join("\n");
 
                 self.results.push(DieStats {
-                    sides: sides,
+                    sides,
                     counts: self.current_counts.clone(),
                     mean,
                     expected_mean,
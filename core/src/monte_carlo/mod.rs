@@ -1,2 +1,7 @@
+pub mod calibration;
 pub mod combat;
+pub mod compare;
+pub mod day;
 pub mod dice;
+pub mod stats;
+pub mod sweep;
@@ -0,0 +1,368 @@
+/// z-score for a two-sided 95% confidence interval
+pub const Z_95: f64 = 1.96;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Default for ConfidenceInterval {
+    fn default() -> Self {
+        ConfidenceInterval {
+            lower: 0.0,
+            upper: 1.0,
+        }
+    }
+}
+
+impl ConfidenceInterval {
+    pub fn width(&self) -> f64 {
+        self.upper - self.lower
+    }
+
+    pub fn contains(&self, value: f64) -> bool {
+        value >= self.lower && value <= self.upper
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistogramBucket {
+    pub lower: usize,
+    pub upper: usize, // inclusive
+    pub count: usize,
+}
+
+/// Collects one integer sample per simulated combat (rounds, HP left, damage
+/// dealt...) so we can report the shape of an outcome and not just its mean.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Distribution {
+    samples: Vec<usize>,
+    sorted: bool,
+}
+
+impl Distribution {
+    pub fn new() -> Self {
+        Distribution::default()
+    }
+
+    pub fn record(&mut self, sample: usize) {
+        self.samples.push(sample);
+        self.sorted = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn samples(&self) -> &Vec<usize> {
+        &self.samples
+    }
+
+    pub fn mean(&self) -> f64 {
+        mean(&self.as_f64())
+    }
+
+    pub fn standard_error(&self) -> f64 {
+        standard_error(&self.as_f64())
+    }
+
+    pub fn min(&self) -> usize {
+        self.samples.iter().copied().min().unwrap_or(0)
+    }
+
+    pub fn max(&self) -> usize {
+        self.samples.iter().copied().max().unwrap_or(0)
+    }
+
+    /// Nearest-rank percentile, `p` in [0, 100]
+    pub fn percentile(&self, p: f64) -> usize {
+        if self.samples.is_empty() {
+            return 0;
+        }
+
+        let rank = ((p / 100.0) * self.samples.len() as f64).ceil() as usize;
+        let index = rank.clamp(1, self.samples.len()) - 1;
+        if self.sorted {
+            self.samples[index]
+        } else {
+            let mut samples = self.samples.clone();
+            samples.sort_unstable();
+            samples[index]
+        }
+    }
+
+    pub fn median(&self) -> usize {
+        self.percentile(50.0)
+    }
+
+    /// Splits the observed range into at most `max_buckets` equal-width
+    /// buckets. Small ranges get one bucket per value.
+    pub fn histogram(&self, max_buckets: usize) -> Vec<HistogramBucket> {
+        if self.samples.is_empty() || max_buckets == 0 {
+            return vec![];
+        }
+
+        let min = self.min();
+        let max = self.max();
+        let width = (max - min + 1).div_ceil(max_buckets);
+        let mut buckets: Vec<HistogramBucket> = (min..=max)
+            .step_by(width)
+            .map(|lower| HistogramBucket {
+                lower,
+                upper: (lower + width - 1).min(max),
+                count: 0,
+            })
+            .collect();
+
+        for sample in &self.samples {
+            buckets[(sample - min) / width].count += 1;
+        }
+
+        buckets
+    }
+
+    /// Sorts the samples once so repeated percentile lookups are cheap
+    pub fn finalize(&mut self) {
+        if !self.sorted {
+            self.samples.sort_unstable();
+            self.sorted = true;
+        }
+    }
+
+    fn as_f64(&self) -> Vec<f64> {
+        self.samples.iter().map(|s| *s as f64).collect()
+    }
+}
+
+/// Wilson score interval for a binomial proportion. Unlike the normal
+/// approximation it stays inside [0, 1] and behaves with 0 or `trials`
+/// successes, which is common for lopsided encounters.
+pub fn wilson_interval(successes: usize, trials: usize, z: f64) -> ConfidenceInterval {
+    if trials == 0 {
+        return ConfidenceInterval::default();
+    }
+
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+    let denominator = 1.0 + z2 / n;
+    let center = (p + z2 / (2.0 * n)) / denominator;
+    let half_width = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / denominator;
+
+    ConfidenceInterval {
+        lower: (center - half_width).max(0.0),
+        upper: (center + half_width).min(1.0),
+    }
+}
+
+pub fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Sample standard deviation (n - 1 denominator)
+pub fn standard_deviation(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(values);
+    let variance =
+        values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64;
+    variance.sqrt()
+}
+
+/// Standard error of the mean
+pub fn standard_error(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    standard_deviation(values) / (values.len() as f64).sqrt()
+}
+
+/// Standard normal CDF, using the Abramowitz & Stegun 7.1.26 approximation
+/// of erf (absolute error below 1.5e-7).
+pub fn normal_cdf(z: f64) -> f64 {
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let erf = 1.0 - poly * (-x * x).exp();
+    if z >= 0.0 {
+        0.5 * (1.0 + erf)
+    } else {
+        0.5 * (1.0 - erf)
+    }
+}
+
+/// Difference in means between two paired samples (A - B), e.g. the same
+/// metric from two encounters simulated with the same seeds. Pairing cancels
+/// out shared luck, so the interval is much tighter than comparing two
+/// independent means.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PairedDifference {
+    pub a_mean: f64,
+    pub b_mean: f64,
+    pub difference: f64,
+    pub ci: ConfidenceInterval,
+    /// Two-sided p-value for the null hypothesis that A and B are the same
+    pub p_value: f64,
+}
+
+impl PairedDifference {
+    pub fn from_pairs(a: &[f64], b: &[f64]) -> Self {
+        assert_eq!(a.len(), b.len(), "paired samples must be the same length");
+
+        let differences: Vec<f64> = a.iter().zip(b.iter()).map(|(a, b)| a - b).collect();
+        let difference = mean(&differences);
+        let std_err = standard_error(&differences);
+        let p_value = if std_err > 0.0 {
+            2.0 * (1.0 - normal_cdf((difference / std_err).abs()))
+        } else if difference == 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+
+        PairedDifference {
+            a_mean: mean(a),
+            b_mean: mean(b),
+            difference,
+            ci: ConfidenceInterval {
+                lower: difference - Z_95 * std_err,
+                upper: difference + Z_95 * std_err,
+            },
+            p_value,
+        }
+    }
+
+    /// Significant at the 5% level
+    pub fn significant(&self) -> bool {
+        self.p_value < 0.05
+    }
+}
+
+#[cfg(test)]
+mod stats_tests {
+    use super::*;
+
+    #[test]
+    fn test_wilson_interval() {
+        let ci = wilson_interval(50, 100, Z_95);
+        assert!((ci.lower - 0.4038).abs() < 0.001);
+        assert!((ci.upper - 0.5962).abs() < 0.001);
+        assert!(ci.contains(0.5));
+
+        // Degenerate proportions stay inside [0, 1] but still have width
+        let ci = wilson_interval(0, 20, Z_95);
+        assert_eq!(ci.lower, 0.0);
+        assert!(ci.upper > 0.0 && ci.upper < 0.2);
+
+        let ci = wilson_interval(20, 20, Z_95);
+        assert_eq!(ci.upper, 1.0);
+        assert!(ci.lower > 0.8);
+
+        // More trials means a narrower interval
+        let narrow = wilson_interval(5000, 10000, Z_95);
+        assert!(narrow.width() < wilson_interval(50, 100, Z_95).width());
+    }
+
+    #[test]
+    fn test_standard_error() {
+        let values = vec![2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(mean(&values), 5.0);
+        assert!((standard_deviation(&values) - 2.138).abs() < 0.001);
+        assert!((standard_error(&values) - 0.756).abs() < 0.001);
+
+        assert_eq!(standard_error(&[]), 0.0);
+        assert_eq!(standard_error(&[3.0]), 0.0);
+    }
+
+    #[test]
+    fn test_normal_cdf() {
+        assert!((normal_cdf(0.0) - 0.5).abs() < 1e-6);
+        assert!((normal_cdf(Z_95) - 0.975).abs() < 1e-4);
+        assert!((normal_cdf(-Z_95) - 0.025).abs() < 1e-4);
+        assert!(normal_cdf(6.0) > 0.999999);
+    }
+
+    #[test]
+    fn test_paired_difference() {
+        // B is always exactly one better than A
+        let a = vec![1.0, 2.0, 3.0, 4.0];
+        let b = vec![2.0, 3.0, 4.0, 5.0];
+        let diff = PairedDifference::from_pairs(&a, &b);
+        assert_eq!(diff.a_mean, 2.5);
+        assert_eq!(diff.b_mean, 3.5);
+        assert_eq!(diff.difference, -1.0);
+        assert_eq!(diff.p_value, 0.0);
+        assert!(diff.significant());
+
+        // identical samples
+        let diff = PairedDifference::from_pairs(&a, &a);
+        assert_eq!(diff.difference, 0.0);
+        assert_eq!(diff.p_value, 1.0);
+        assert!(!diff.significant());
+
+        // noisy differences centred on zero
+        let a = vec![1.0, 0.0, 1.0, 0.0, 1.0, 0.0];
+        let b = vec![0.0, 1.0, 0.0, 1.0, 1.0, 0.0];
+        let diff = PairedDifference::from_pairs(&a, &b);
+        assert_eq!(diff.difference, 0.0);
+        assert!(diff.ci.contains(0.0));
+        assert!(!diff.significant());
+    }
+
+    #[test]
+    fn test_distribution_percentiles() {
+        let mut distribution = Distribution::new();
+        for sample in [5, 1, 4, 2, 3, 10, 9, 8, 7, 6] {
+            distribution.record(sample);
+        }
+
+        assert_eq!(distribution.len(), 10);
+        assert_eq!(distribution.mean(), 5.5);
+        assert_eq!(distribution.min(), 1);
+        assert_eq!(distribution.max(), 10);
+        assert_eq!(distribution.median(), 5);
+        assert_eq!(distribution.percentile(90.0), 9);
+        assert_eq!(distribution.percentile(100.0), 10);
+        assert_eq!(distribution.percentile(0.0), 1);
+
+        // Same answers once sorted
+        distribution.finalize();
+        assert_eq!(distribution.median(), 5);
+        assert_eq!(distribution.percentile(90.0), 9);
+    }
+
+    #[test]
+    fn test_distribution_histogram() {
+        let mut distribution = Distribution::new();
+        for sample in [1, 1, 2, 3, 3, 3] {
+            distribution.record(sample);
+        }
+
+        let histogram = distribution.histogram(10);
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(histogram[0].count, 2);
+        assert_eq!(histogram[1].count, 1);
+        assert_eq!(histogram[2].count, 3);
+
+        // Wide ranges get grouped
+        distribution.record(20);
+        let histogram = distribution.histogram(4);
+        assert_eq!(histogram.len(), 4);
+        assert_eq!(histogram[0].lower, 1);
+        assert_eq!(histogram[0].upper, 5);
+        assert_eq!(histogram[0].count, 6);
+        assert_eq!(histogram[3].count, 1);
+        assert_eq!(histogram.iter().map(|b| b.count).sum::<usize>(), 7);
+    }
+}
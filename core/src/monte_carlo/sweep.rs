@@ -0,0 +1,254 @@
+use crate::{
+    dnd::proficiency_bonus,
+    encounter::{Encounter, EncounterError},
+    Action,
+};
+
+use super::combat::{encounter_monte_carlo_iterator, CombatMonteCarloIterator, CombatStats};
+
+/// A knob on an encounter that a sweep turns across a range of values.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SweepParameter {
+    /// How many of the named monster are in the fight
+    MonsterCount(String),
+    /// Every hero's level. HP scales linearly with level and attack bonuses
+    /// follow the proficiency bonus.
+    PartyLevel,
+    MonsterAc(String),
+    MonsterHp(String),
+    /// Hit bonus of every attack the named monster has
+    MonsterHitBonus(String),
+}
+
+impl SweepParameter {
+    pub fn description(&self) -> String {
+        match self {
+            SweepParameter::MonsterCount(name) => format!("Number of {}", name),
+            SweepParameter::PartyLevel => "Party Level".into(),
+            SweepParameter::MonsterAc(name) => format!("{} AC", name),
+            SweepParameter::MonsterHp(name) => format!("{} HP", name),
+            SweepParameter::MonsterHitBonus(name) => format!("{} Hit Bonus", name),
+        }
+    }
+
+    /// Returns a copy of `encounter` with this parameter set to `value`
+    pub fn apply(&self, encounter: &Encounter, value: isize) -> Result<Encounter, EncounterError> {
+        let mut encounter = encounter.clone();
+        match self {
+            SweepParameter::MonsterCount(name) => {
+                encounter.monster_mut(name)?.count = non_negative(self, value)?;
+            }
+            SweepParameter::PartyLevel => {
+                let level = non_negative(self, value)?;
+                if !(1..=20).contains(&level) {
+                    return Err(EncounterError::InvalidParameter(format!(
+                        "party level must be between 1 and 20, got {}",
+                        level
+                    )));
+                }
+                // HP is rescaled from each hero's current level
+                encounter.validate()?;
+                for hero in encounter.heroes.iter_mut() {
+                    let bonus_change = proficiency_bonus(level) - proficiency_bonus(hero.level);
+                    hero.hp = (hero.hp * level + hero.level / 2) / hero.level;
+                    hero.level = level;
+                    for action in hero.actions.iter_mut() {
+                        if let Action::Attack { hit_bonus, .. } = action {
+                            *hit_bonus += bonus_change;
+                        }
+                    }
+                }
+            }
+            SweepParameter::MonsterAc(name) => {
+                encounter.monster_mut(name)?.ac = non_negative(self, value)?;
+            }
+            SweepParameter::MonsterHp(name) => {
+                encounter.monster_mut(name)?.hp = non_negative(self, value)?;
+            }
+            SweepParameter::MonsterHitBonus(name) => {
+                for action in encounter.monster_mut(name)?.actions.iter_mut() {
+                    if let Action::Attack { hit_bonus, .. } = action {
+                        *hit_bonus = value;
+                    }
+                }
+            }
+        }
+        Ok(encounter)
+    }
+}
+
+fn non_negative(parameter: &SweepParameter, value: isize) -> Result<usize, EncounterError> {
+    usize::try_from(value).map_err(|_| {
+        EncounterError::InvalidParameter(format!(
+            "{} can't be negative, got {}",
+            parameter.description(),
+            value
+        ))
+    })
+}
+
+pub struct SweepPoint {
+    pub value: isize,
+    pub encounter: Encounter,
+    pub stats: CombatStats,
+}
+
+/// Runs a Monte Carlo batch of `iterations` combats for each value of
+/// `parameter`. Every encounter is built up front so a bad parameter fails
+/// before any simulation starts.
+pub fn sweep_iterator(
+    encounter: &Encounter,
+    parameter: SweepParameter,
+    values: Vec<isize>,
+    iterations: usize,
+) -> Result<SweepIterator, EncounterError> {
+    let points = values
+        .into_iter()
+        .map(|value| Ok((value, parameter.apply(encounter, value)?)))
+        .collect::<Result<Vec<_>, EncounterError>>()?;
+
+    Ok(SweepIterator {
+        parameter,
+        points,
+        iterations,
+        point_index: 0,
+        current: None,
+        results: vec![],
+    })
+}
+
+pub struct SweepIterator {
+    pub parameter: SweepParameter,
+    points: Vec<(isize, Encounter)>,
+    iterations: usize,
+    point_index: usize,
+    current: Option<CombatMonteCarloIterator>,
+
+    pub results: Vec<SweepPoint>,
+}
+
+impl SweepIterator {
+    pub fn total_iterations(&self) -> usize {
+        self.points.len() * self.iterations
+    }
+}
+
+impl Iterator for SweepIterator {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.point_index < self.points.len() {
+            let encounter = &self.points[self.point_index].1;
+            let current = self.current.get_or_insert_with(|| {
+                encounter_monte_carlo_iterator(self.iterations, encounter.clone())
+            });
+
+            if let Some(iteration) = current.next() {
+                return Some((self.point_index, iteration));
+            }
+
+            // this point is finished, move its stats into the results
+            let finished = self.current.take().unwrap();
+            let (value, encounter) = self.points[self.point_index].clone();
+            self.results.push(SweepPoint {
+                value,
+                encounter,
+                stats: finished.stats,
+            });
+            self.point_index += 1;
+        }
+
+        None
+    }
+
+    fn count(self) -> usize
+    where
+        Self: Sized,
+    {
+        self.total_iterations()
+    }
+}
+
+#[cfg(test)]
+mod sweep_tests {
+    use super::*;
+    use crate::combat::level_one_encounter;
+
+    #[test]
+    fn test_apply_parameters() {
+        let encounter = level_one_encounter(4);
+
+        let swept = SweepParameter::MonsterCount("Kobold".into())
+            .apply(&encounter, 7)
+            .unwrap();
+        assert_eq!(swept.monster_crs().len(), 7);
+
+        let swept = SweepParameter::MonsterAc("Kobold".into())
+            .apply(&encounter, 15)
+            .unwrap();
+        assert_eq!(swept.monsters[0].ac, 15);
+
+        let swept = SweepParameter::MonsterHitBonus("Kobold".into())
+            .apply(&encounter, 7)
+            .unwrap();
+        assert!(swept.monsters[0]
+            .actions
+            .iter()
+            .all(|a| matches!(a, Action::Attack { hit_bonus: 7, .. })));
+
+        let swept = SweepParameter::PartyLevel.apply(&encounter, 5).unwrap();
+        assert_eq!(swept.hero_levels(), vec![5, 5, 5]);
+        assert_eq!(swept.heroes[0].hp, 60);
+        // proficiency bonus goes from +2 to +3
+        assert!(matches!(
+            swept.heroes[0].actions[0],
+            Action::Attack { hit_bonus: 6, .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_parameters() {
+        let encounter = level_one_encounter(4);
+
+        assert!(matches!(
+            SweepParameter::MonsterCount("Dragon".into()).apply(&encounter, 1),
+            Err(EncounterError::UnknownCreature(_))
+        ));
+        assert!(matches!(
+            SweepParameter::MonsterHp("Kobold".into()).apply(&encounter, -1),
+            Err(EncounterError::InvalidParameter(_))
+        ));
+        assert!(matches!(
+            SweepParameter::PartyLevel.apply(&encounter, 21),
+            Err(EncounterError::InvalidParameter(_))
+        ));
+
+        let mut levelless = encounter.clone();
+        levelless.heroes[0].level = 0;
+        assert!(matches!(
+            SweepParameter::PartyLevel.apply(&levelless, 5),
+            Err(EncounterError::InvalidParameter(_))
+        ));
+    }
+
+    #[test]
+    fn test_sweep() {
+        let encounter = level_one_encounter(1);
+        let mut iterator = sweep_iterator(
+            &encounter,
+            SweepParameter::MonsterCount("Kobold".into()),
+            vec![1, 2, 3],
+            20,
+        )
+        .unwrap();
+        let steps = iterator.by_ref().count();
+
+        assert_eq!(steps, 60);
+        assert_eq!(iterator.results.len(), 3);
+        for (point, value) in iterator.results.iter().zip([1, 2, 3]) {
+            assert_eq!(point.value, value);
+            assert_eq!(point.stats.iterations, 20);
+            assert_eq!(point.encounter.monster_crs().len(), value as usize);
+        }
+    }
+}
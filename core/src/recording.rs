@@ -0,0 +1,156 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{encounter::Encounter, seed_rng, Combat, Event, EventLog};
+
+#[derive(Error, Debug)]
+pub enum RecordingError {
+    #[error("couldn't read or write recording file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("couldn't parse recording file: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("couldn't write recording: {0}")]
+    Serialize(#[from] toml::ser::Error),
+}
+
+/// One fight written down: the encounter it started from, the seed its dice
+/// were rolled from and everything that happened. The seed and encounter
+/// are enough to play it again; the events are there to check that it did
+/// play out the same and to read without replaying.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    #[serde(with = "seed_string")]
+    pub seed: u64,
+    pub encounter: Encounter,
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+/// A recording played again
+pub struct Replay {
+    pub combat: Combat,
+    pub events: Vec<Event>,
+    /// Index of the first event that came out differently from the
+    /// recording, e.g. after the engine changed
+    pub diverged_at: Option<usize>,
+}
+
+impl Recording {
+    /// Runs `encounter` from `seed`, the way Monte Carlo batches do, and
+    /// hands back the finished fight with it
+    pub fn record(encounter: &Encounter, seed: u64) -> (Self, Combat) {
+        let (combat, events) = run(encounter, seed);
+        let recording = Recording {
+            seed,
+            encounter: encounter.clone(),
+            events,
+        };
+        (recording, combat)
+    }
+
+    pub fn replay(&self) -> Replay {
+        let (combat, events) = run(&self.encounter, self.seed);
+        let diverged_at = events
+            .iter()
+            .zip(&self.events)
+            .position(|(replayed, recorded)| replayed != recorded)
+            .or_else(|| {
+                (events.len() != self.events.len()).then(|| events.len().min(self.events.len()))
+            });
+        Replay {
+            combat,
+            events,
+            diverged_at,
+        }
+    }
+
+    pub fn from_toml_str(s: &str) -> Result<Self, RecordingError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn to_toml_string(&self) -> Result<String, RecordingError> {
+        Ok(toml::to_string(self)?)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, RecordingError> {
+        Recording::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), RecordingError> {
+        Ok(std::fs::write(path, self.to_toml_string()?)?)
+    }
+}
+
+/// TOML integers are signed 64-bit, too small for half of all seeds, so
+/// seeds are written as strings. Integer seeds still load.
+mod seed_string {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(seed: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&seed.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Seed {
+            Number(u64),
+            Text(String),
+        }
+
+        match Seed::deserialize(deserializer)? {
+            Seed::Number(seed) => Ok(seed),
+            Seed::Text(seed) => seed.parse().map_err(D::Error::custom),
+        }
+    }
+}
+
+fn run(encounter: &Encounter, seed: u64) -> (Combat, Vec<Event>) {
+    seed_rng(seed);
+    let mut combat = encounter.build_combat();
+    let log = EventLog::new();
+    combat.observe(log.clone());
+    combat.run();
+    (combat, log.events())
+}
+
+#[cfg(test)]
+mod recording_tests {
+    use super::*;
+    use crate::combat::level_one_encounter;
+
+    #[test]
+    fn test_round_trip_and_replay() {
+        let (recording, combat) = Recording::record(&level_one_encounter(4), 42);
+        assert!(!combat.is_ongoing());
+        assert!(!recording.events.is_empty());
+
+        let text = recording.to_toml_string().unwrap();
+        let loaded = Recording::from_toml_str(&text).unwrap();
+        assert_eq!(loaded, recording);
+
+        let replay = loaded.replay();
+        assert_eq!(replay.diverged_at, None);
+        assert_eq!(replay.events, recording.events);
+
+        // A recording from another seed doesn't match what this one rolls
+        let mut tampered = recording.clone();
+        tampered.seed = 43;
+        assert!(tampered.replay().diverged_at.is_some());
+    }
+
+    #[test]
+    fn test_seeds_past_i64() {
+        let (recording, _) = Recording::record(&level_one_encounter(2), u64::MAX);
+        let text = recording.to_toml_string().unwrap();
+        let loaded = Recording::from_toml_str(&text).unwrap();
+        assert_eq!(loaded.seed, u64::MAX);
+        assert_eq!(loaded.replay().diverged_at, None);
+
+        // Recordings written with an integer seed still load
+        let text = text.replace("seed = \"18446744073709551615\"", "seed = 7");
+        assert_eq!(Recording::from_toml_str(&text).unwrap().seed, 7);
+    }
+}
@@ -1,5 +1,28 @@
+/// Which roster a creature is kept and reported under: the party whose
+/// results are measured, or everyone else. Who fights whom is up to
+/// factions.
 #[derive(Clone, PartialEq, Debug, Copy)]
 pub enum Team {
     Heroes,
     Monsters,
 }
+
+impl Team {
+    /// The faction creatures on this roster belong to unless they're given
+    /// another
+    pub fn faction(&self) -> &'static str {
+        match self {
+            Team::Heroes => "heroes",
+            Team::Monsters => "monsters",
+        }
+    }
+}
+
+/// Creatures in different factions are hostile to each other unless their
+/// factions are allied
+pub fn hostile(alliances: &[(String, String)], a: &str, b: &str) -> bool {
+    a != b
+        && !alliances
+            .iter()
+            .any(|(x, y)| (x == a && y == b) || (x == b && y == a))
+}